@@ -0,0 +1,2 @@
+DROP TABLE "cached_tasks";
+DROP TABLE "pending_operations";
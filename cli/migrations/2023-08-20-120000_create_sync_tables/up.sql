@@ -0,0 +1,13 @@
+CREATE TABLE "pending_operations" (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    kind TEXT NOT NULL,
+    task_name TEXT NOT NULL,
+    timestamp BIGINT NOT NULL
+);
+
+CREATE TABLE "cached_tasks" (
+    name TEXT PRIMARY KEY NOT NULL,
+    duration BIGINT NOT NULL,
+    started_at BIGINT NOT NULL,
+    finished_at BIGINT
+);
@@ -0,0 +1,14 @@
+CREATE TABLE "tasks_without_uploaded" (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name TEXT NOT NULL,
+    started_at BIGINT NOT NULL,
+    finished_at BIGINT
+);
+INSERT INTO "tasks_without_uploaded" SELECT id, name, started_at, finished_at FROM "tasks";
+DROP TABLE "tasks";
+ALTER TABLE "tasks_without_uploaded" RENAME TO "tasks";
+
+CREATE INDEX tasks_name_idx on tasks (name);
+CREATE INDEX tasks_name_started_at_idx on tasks (name, started_at);
+CREATE INDEX tasks_name_finished_at_idx on tasks (name, finished_at);
+CREATE INDEX tasks_name_started_at_finished_at_idx on tasks (name, started_at, finished_at);
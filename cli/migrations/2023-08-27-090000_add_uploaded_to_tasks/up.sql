@@ -0,0 +1 @@
+ALTER TABLE "tasks" ADD COLUMN uploaded BOOLEAN NOT NULL DEFAULT 0;
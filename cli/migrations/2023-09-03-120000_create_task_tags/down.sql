@@ -0,0 +1,13 @@
+CREATE TABLE "pending_operations_without_tags" (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    kind TEXT NOT NULL,
+    task_name TEXT NOT NULL,
+    timestamp BIGINT NOT NULL
+);
+INSERT INTO "pending_operations_without_tags"
+    SELECT id, kind, task_name, timestamp FROM "pending_operations";
+DROP TABLE "pending_operations";
+ALTER TABLE "pending_operations_without_tags" RENAME TO "pending_operations";
+
+DROP INDEX task_tags_tag_idx;
+DROP TABLE "task_tags";
@@ -0,0 +1,9 @@
+CREATE TABLE "task_tags" (
+    task_id INTEGER NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (task_id, tag)
+);
+
+CREATE INDEX task_tags_tag_idx on task_tags (tag);
+
+ALTER TABLE "pending_operations" ADD COLUMN tags TEXT NOT NULL DEFAULT '';
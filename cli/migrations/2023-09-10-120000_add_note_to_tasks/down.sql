@@ -0,0 +1,27 @@
+CREATE TABLE "tasks_without_note" (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name TEXT NOT NULL,
+    started_at BIGINT NOT NULL,
+    finished_at BIGINT,
+    uploaded BOOLEAN NOT NULL DEFAULT 0
+);
+INSERT INTO "tasks_without_note" SELECT id, name, started_at, finished_at, uploaded FROM "tasks";
+DROP TABLE "tasks";
+ALTER TABLE "tasks_without_note" RENAME TO "tasks";
+
+CREATE INDEX tasks_name_idx on tasks (name);
+CREATE INDEX tasks_name_started_at_idx on tasks (name, started_at);
+CREATE INDEX tasks_name_finished_at_idx on tasks (name, finished_at);
+CREATE INDEX tasks_name_started_at_finished_at_idx on tasks (name, started_at, finished_at);
+
+CREATE TABLE "pending_operations_without_note" (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    kind TEXT NOT NULL,
+    task_name TEXT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    tags TEXT NOT NULL DEFAULT ''
+);
+INSERT INTO "pending_operations_without_note"
+    SELECT id, kind, task_name, timestamp, tags FROM "pending_operations";
+DROP TABLE "pending_operations";
+ALTER TABLE "pending_operations_without_note" RENAME TO "pending_operations";
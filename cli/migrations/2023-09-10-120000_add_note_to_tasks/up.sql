@@ -0,0 +1,3 @@
+ALTER TABLE "tasks" ADD COLUMN note TEXT;
+
+ALTER TABLE "pending_operations" ADD COLUMN note TEXT;
@@ -0,0 +1,12 @@
+CREATE TABLE "pending_operations_without_manual_entries" (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    kind TEXT NOT NULL,
+    task_name TEXT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    tags TEXT NOT NULL DEFAULT '',
+    note TEXT
+);
+INSERT INTO "pending_operations_without_manual_entries"
+    SELECT id, kind, task_name, timestamp, tags, note FROM "pending_operations";
+DROP TABLE "pending_operations";
+ALTER TABLE "pending_operations_without_manual_entries" RENAME TO "pending_operations";
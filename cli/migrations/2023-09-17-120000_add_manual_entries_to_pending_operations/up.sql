@@ -0,0 +1,3 @@
+ALTER TABLE "pending_operations" ADD COLUMN finished_at BIGINT;
+
+ALTER TABLE "pending_operations" ADD COLUMN force BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,43 @@
+CREATE TABLE "tasks_without_pomodoro" (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name TEXT NOT NULL,
+    started_at BIGINT NOT NULL,
+    finished_at BIGINT,
+    uploaded BOOLEAN NOT NULL DEFAULT 0,
+    note TEXT
+);
+INSERT INTO "tasks_without_pomodoro"
+    SELECT id, name, started_at, finished_at, uploaded, note FROM "tasks";
+DROP TABLE "tasks";
+ALTER TABLE "tasks_without_pomodoro" RENAME TO "tasks";
+
+CREATE INDEX tasks_name_idx on tasks (name);
+CREATE INDEX tasks_name_started_at_idx on tasks (name, started_at);
+CREATE INDEX tasks_name_finished_at_idx on tasks (name, finished_at);
+CREATE INDEX tasks_name_started_at_finished_at_idx on tasks (name, started_at, finished_at);
+
+CREATE TABLE "pending_operations_without_pomodoro" (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    kind TEXT NOT NULL,
+    task_name TEXT NOT NULL,
+    timestamp BIGINT NOT NULL,
+    tags TEXT NOT NULL DEFAULT '',
+    note TEXT,
+    finished_at BIGINT,
+    force BOOLEAN NOT NULL DEFAULT 0
+);
+INSERT INTO "pending_operations_without_pomodoro"
+    SELECT id, kind, task_name, timestamp, tags, note, finished_at, force FROM "pending_operations";
+DROP TABLE "pending_operations";
+ALTER TABLE "pending_operations_without_pomodoro" RENAME TO "pending_operations";
+
+CREATE TABLE "cached_tasks_without_pomodoros" (
+    name TEXT PRIMARY KEY NOT NULL,
+    duration BIGINT NOT NULL,
+    started_at BIGINT NOT NULL,
+    finished_at BIGINT
+);
+INSERT INTO "cached_tasks_without_pomodoros"
+    SELECT name, duration, started_at, finished_at FROM "cached_tasks";
+DROP TABLE "cached_tasks";
+ALTER TABLE "cached_tasks_without_pomodoros" RENAME TO "cached_tasks";
@@ -0,0 +1,5 @@
+ALTER TABLE "tasks" ADD COLUMN pomodoro BOOLEAN NOT NULL DEFAULT 0;
+
+ALTER TABLE "pending_operations" ADD COLUMN pomodoro BOOLEAN NOT NULL DEFAULT 0;
+
+ALTER TABLE "cached_tasks" ADD COLUMN pomodoros BIGINT NOT NULL DEFAULT 0;
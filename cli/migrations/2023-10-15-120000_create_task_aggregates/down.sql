@@ -0,0 +1,2 @@
+DROP INDEX task_aggregates_day_idx;
+DROP TABLE "task_aggregates";
@@ -0,0 +1,14 @@
+-- compacted sessions, one row per task, local day and set of tags
+CREATE TABLE "task_aggregates" (
+    name TEXT NOT NULL,
+    day BIGINT NOT NULL,
+    tags TEXT NOT NULL DEFAULT '',
+    started_at BIGINT NOT NULL,
+    finished_at BIGINT NOT NULL,
+    duration BIGINT NOT NULL,
+    sessions BIGINT NOT NULL,
+    pomodoros BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (name, day, tags)
+);
+
+CREATE INDEX task_aggregates_day_idx on task_aggregates (day);
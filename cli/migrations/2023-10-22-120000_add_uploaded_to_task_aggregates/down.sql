@@ -0,0 +1,20 @@
+CREATE TABLE "task_aggregates_without_uploaded" (
+    name TEXT NOT NULL,
+    day BIGINT NOT NULL,
+    tags TEXT NOT NULL DEFAULT '',
+    started_at BIGINT NOT NULL,
+    finished_at BIGINT NOT NULL,
+    duration BIGINT NOT NULL,
+    sessions BIGINT NOT NULL,
+    pomodoros BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (name, day, tags)
+);
+INSERT INTO "task_aggregates_without_uploaded"
+    SELECT name, day, tags, max(started_at), max(finished_at), sum(duration), sum(sessions), sum(pomodoros)
+    FROM "task_aggregates"
+    GROUP BY name, day, tags;
+DROP INDEX task_aggregates_day_idx;
+DROP TABLE "task_aggregates";
+ALTER TABLE "task_aggregates_without_uploaded" RENAME TO "task_aggregates";
+
+CREATE INDEX task_aggregates_day_idx on task_aggregates (day);
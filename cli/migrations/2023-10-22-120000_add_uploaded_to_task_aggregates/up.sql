@@ -0,0 +1,22 @@
+-- the days compacted from sessions recorded while logged out are kept apart
+-- until they were uploaded
+CREATE TABLE "task_aggregates_with_uploaded" (
+    name TEXT NOT NULL,
+    day BIGINT NOT NULL,
+    tags TEXT NOT NULL DEFAULT '',
+    started_at BIGINT NOT NULL,
+    finished_at BIGINT NOT NULL,
+    duration BIGINT NOT NULL,
+    sessions BIGINT NOT NULL,
+    pomodoros BIGINT NOT NULL DEFAULT 0,
+    uploaded BOOLEAN NOT NULL DEFAULT 0,
+    PRIMARY KEY (name, day, tags, uploaded)
+);
+INSERT INTO "task_aggregates_with_uploaded"
+    SELECT name, day, tags, started_at, finished_at, duration, sessions, pomodoros, 1
+    FROM "task_aggregates";
+DROP INDEX task_aggregates_day_idx;
+DROP TABLE "task_aggregates";
+ALTER TABLE "task_aggregates_with_uploaded" RENAME TO "task_aggregates";
+
+CREATE INDEX task_aggregates_day_idx on task_aggregates (day);
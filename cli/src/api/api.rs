@@ -1,6 +1,7 @@
-use reqwest::{blocking, Result, StatusCode};
 use serde::{Deserialize, Serialize};
 
+use super::client::{empty_response, json_response, send, text_response, ApiError};
+
 #[derive(Deserialize, Serialize)]
 pub struct FetchAccessTokenPayload {
     access_token: String,
@@ -9,6 +10,57 @@ pub struct FetchAccessTokenPayload {
 #[derive(Deserialize, Serialize)]
 pub struct TaskInfoPayload {
     name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<i64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pomodoro: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ManualTaskPayload {
+    pub name: String,
+    pub started_at: i64,
+    pub finished_at: i64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub force: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SessionUpdatePayload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<i64>,
+    pub force: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct APIImportedTask {
+    pub name: String,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+    pub pomodoro: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TaskImportPayload {
+    tasks: Vec<APIImportedTask>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct APIImportReport {
+    pub imported: usize,
+    pub skipped: usize,
+    pub duration: i64,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -17,6 +69,31 @@ pub struct APITaskInfo {
     pub name: String,
     pub started_at: i64,
     pub finished_at: Option<i64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub pomodoro: bool,
+    // only filled in when punching in closed other tasks
+    #[serde(default)]
+    pub stopped: Vec<APITaskInfo>,
+    // only filled in when a session was edited
+    #[serde(default)]
+    pub previous_started_at: Option<i64>,
+    #[serde(default)]
+    pub previous_finished_at: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct APIUserSettings {
+    pub task_policy: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct APISwitchResult {
+    pub stopped: Vec<APITaskInfo>,
+    pub started: APITaskInfo,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -24,6 +101,15 @@ pub struct APITaskStat {
     pub name: String,
     pub status: String,
     pub duration: i64,
+    #[serde(default)]
+    pub pomodoros: i64,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct APIPeriodReportRow {
+    pub period_start: i64,
+    pub name: String,
+    pub duration: i64,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -32,139 +118,245 @@ pub struct APITaskListItem {
     pub duration: i64,
     pub started_at: i64,
     pub finished_at: Option<i64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub pomodoros: i64,
 }
 
-pub fn fetch_access_token(api_endpoint: &str, access_token: &str) -> String {
-    let res = blocking::Client::new()
-        .post(api_endpoint)
-        .json(&FetchAccessTokenPayload {
-            access_token: access_token.to_string(),
-        })
-        .send()
-        .expect("failed to fetch API access token");
-    res.text()
-        .expect("failed to parse API access token response")
+pub fn fetch_access_token(api_endpoint: &str, access_token: &str) -> Result<String, ApiError> {
+    let payload = FetchAccessTokenPayload {
+        access_token: access_token.to_string(),
+    };
+    let resp = send(|client| client.post(api_endpoint).json(&payload), false)?;
+    text_response(resp)
 }
 
-pub fn verify_access_token(api_endpoint: &str, access_token: &str) -> Result<bool> {
-    let res = blocking::Client::new()
-        .post(api_endpoint)
-        .header("Authorization", format!("Bearer {}", access_token))
-        .send()
-        .expect("request to verify the token failed");
-    Ok(res.status() == StatusCode::NO_CONTENT)
+pub fn verify_access_token(api_endpoint: &str, access_token: &str) -> Result<bool, ApiError> {
+    let resp = send(
+        |client| client.post(api_endpoint).bearer_auth(access_token),
+        true,
+    )?;
+    // only a refused token is invalid, any other failure leaves it unverified
+    match empty_response(resp) {
+        Ok(_) => Ok(true),
+        Err(ApiError::Auth(_)) => Ok(false),
+        Err(err) => Err(err),
+    }
 }
 
 pub fn start_task(
     api_endpoint: String,
     access_token: String,
     task_name: String,
-) -> std::result::Result<APITaskInfo, String> {
-    let res = blocking::Client::new()
-        .post(api_endpoint)
-        .json(&TaskInfoPayload { name: task_name })
-        .header("Authorization", format!("Bearer {}", access_token))
-        .send();
-    match res {
-        Ok(resp) => match resp.status() {
-            StatusCode::OK => resp
-                .json::<APITaskInfo>()
-                .map_err(|e| format!("failed to parse the response: {}", e)),
-            _ => match resp.text() {
-                Ok(err_msg) => Err(err_msg),
-                Err(err) => Err(format!("failed to parse the response: {}", err)),
-            },
+    timestamp: i64,
+    tags: Vec<String>,
+) -> Result<APITaskInfo, ApiError> {
+    let payload = TaskInfoPayload {
+        name: task_name,
+        timestamp: Some(timestamp),
+        tags,
+        note: None,
+        pomodoro: false,
+    };
+    let resp = send(
+        |client| {
+            client
+                .post(&api_endpoint)
+                .json(&payload)
+                .bearer_auth(&access_token)
         },
-        Err(err) => Err(err.to_string()),
-    }
+        false,
+    )?;
+    json_response(resp)
 }
 
 pub fn finish_task(
     api_endpoint: String,
     access_token: String,
     task_name: String,
-) -> std::result::Result<APITaskInfo, String> {
-    let res = blocking::Client::new()
-        .post(api_endpoint)
-        .json(&TaskInfoPayload { name: task_name })
-        .header("Authorization", format!("Bearer {}", access_token))
-        .send();
-    match res {
-        Ok(resp) => match resp.status() {
-            StatusCode::OK => resp
-                .json::<APITaskInfo>()
-                .map_err(|e| format!("failed to parse the response: {}", e)),
-            _ => match resp.text() {
-                Ok(err_msg) => Err(err_msg),
-                Err(err) => Err(format!("failed to parse the response: {}", err)),
-            },
+    timestamp: i64,
+    note: Option<String>,
+    pomodoro: bool,
+) -> Result<APITaskInfo, ApiError> {
+    let payload = TaskInfoPayload {
+        name: task_name,
+        timestamp: Some(timestamp),
+        tags: vec![],
+        note,
+        pomodoro,
+    };
+    let resp = send(
+        |client| {
+            client
+                .post(&api_endpoint)
+                .json(&payload)
+                .bearer_auth(&access_token)
         },
-        Err(err) => Err(err.to_string()),
-    }
+        false,
+    )?;
+    json_response(resp)
 }
 
-pub fn cancel_task(
+pub fn switch_task(
     api_endpoint: String,
     access_token: String,
     task_name: String,
-) -> std::result::Result<(), String> {
-    let res = blocking::Client::new()
-        .post(api_endpoint)
-        .json(&TaskInfoPayload { name: task_name })
-        .header("Authorization", format!("Bearer {}", access_token))
-        .send();
-    match res {
-        Ok(resp) => match resp.status() {
-            StatusCode::NO_CONTENT => Ok(()),
-            _ => match resp.text() {
-                Ok(err_msg) => Err(err_msg),
-                Err(err) => Err(format!("failed to parse the response: {}", err)),
-            },
+    timestamp: i64,
+    tags: Vec<String>,
+) -> Result<APISwitchResult, ApiError> {
+    let payload = TaskInfoPayload {
+        name: task_name,
+        timestamp: Some(timestamp),
+        tags,
+        note: None,
+        pomodoro: false,
+    };
+    let resp = send(
+        |client| {
+            client
+                .post(&api_endpoint)
+                .json(&payload)
+                .bearer_auth(&access_token)
         },
-        Err(err) => Err(err.to_string()),
-    }
+        false,
+    )?;
+    json_response(resp)
+}
+
+pub fn add_task(
+    api_endpoint: &str,
+    access_token: &str,
+    task: ManualTaskPayload,
+) -> Result<APITaskInfo, ApiError> {
+    let resp = send(
+        |client| {
+            client
+                .post(api_endpoint)
+                .json(&task)
+                .bearer_auth(access_token)
+        },
+        false,
+    )?;
+    json_response(resp)
 }
 
-pub fn get_task(
+pub fn cancel_task(
     api_endpoint: String,
     access_token: String,
-) -> std::result::Result<APITaskStat, String> {
-    let res = blocking::Client::new()
-        .get(api_endpoint)
-        .header("Authorization", format!("Bearer {}", access_token))
-        .send();
-    match res {
-        Ok(resp) => match resp.status() {
-            StatusCode::OK => resp
-                .json::<APITaskStat>()
-                .map_err(|e| format!("failed to parse the response: {}", e)),
-            _ => match resp.text() {
-                Ok(err_msg) => Err(err_msg),
-                Err(err) => Err(format!("failed to parse the response: {}", err)),
-            },
+    task_name: String,
+) -> Result<(), ApiError> {
+    let payload = TaskInfoPayload {
+        name: task_name,
+        timestamp: None,
+        tags: vec![],
+        note: None,
+        pomodoro: false,
+    };
+    let resp = send(
+        |client| {
+            client
+                .post(&api_endpoint)
+                .json(&payload)
+                .bearer_auth(&access_token)
         },
-        Err(err) => Err(err.to_string()),
-    }
+        false,
+    )?;
+    empty_response(resp)
+}
+
+pub fn get_task(api_endpoint: String, access_token: String) -> Result<APITaskStat, ApiError> {
+    let resp = send(
+        |client| client.get(&api_endpoint).bearer_auth(&access_token),
+        true,
+    )?;
+    json_response(resp)
 }
 
-pub fn list_task(
+pub fn list_task(api_endpoint: &str, access_token: &str) -> Result<Vec<APITaskListItem>, ApiError> {
+    let resp = send(
+        |client| client.get(api_endpoint).bearer_auth(access_token),
+        true,
+    )?;
+    json_response(resp)
+}
+
+pub fn get_period_report(
     api_endpoint: &str,
     access_token: &str,
-) -> std::result::Result<Vec<APITaskListItem>, String> {
-    let res = blocking::Client::new()
-        .get(api_endpoint)
-        .header("Authorization", format!("Bearer {}", access_token))
-        .send();
-    match res {
-        Ok(resp) => match resp.status() {
-            StatusCode::OK => resp
-                .json::<Vec<APITaskListItem>>()
-                .map_err(|e| format!("failed to parse the response: {}", e)),
-            _ => match resp.text() {
-                Ok(err_msg) => Err(err_msg),
-                Err(err) => Err(format!("failed to parse the response: {}", err)),
-            },
+) -> Result<Vec<APIPeriodReportRow>, ApiError> {
+    let resp = send(
+        |client| client.get(api_endpoint).bearer_auth(access_token),
+        true,
+    )?;
+    json_response(resp)
+}
+
+pub fn list_sessions(api_endpoint: &str, access_token: &str) -> Result<Vec<APITaskInfo>, ApiError> {
+    let resp = send(
+        |client| client.get(api_endpoint).bearer_auth(access_token),
+        true,
+    )?;
+    json_response(resp)
+}
+
+// the new times are absolute, sending them twice changes nothing
+pub fn update_session(
+    api_endpoint: &str,
+    access_token: &str,
+    update: SessionUpdatePayload,
+) -> Result<APITaskInfo, ApiError> {
+    let resp = send(
+        |client| {
+            client
+                .patch(api_endpoint)
+                .json(&update)
+                .bearer_auth(access_token)
         },
-        Err(err) => Err(err.to_string()),
-    }
+        true,
+    )?;
+    json_response(resp)
+}
+
+pub fn import_tasks(
+    api_endpoint: &str,
+    access_token: &str,
+    tasks: Vec<APIImportedTask>,
+) -> Result<APIImportReport, ApiError> {
+    let payload = TaskImportPayload { tasks };
+    let resp = send(
+        |client| {
+            client
+                .post(api_endpoint)
+                .json(&payload)
+                .bearer_auth(access_token)
+        },
+        false,
+    )?;
+    json_response(resp)
+}
+
+pub fn get_settings(api_endpoint: &str, access_token: &str) -> Result<APIUserSettings, ApiError> {
+    let resp = send(
+        |client| client.get(api_endpoint).bearer_auth(access_token),
+        true,
+    )?;
+    json_response(resp)
+}
+
+pub fn update_settings(
+    api_endpoint: &str,
+    access_token: &str,
+    settings: APIUserSettings,
+) -> Result<APIUserSettings, ApiError> {
+    let resp = send(
+        |client| {
+            client
+                .put(api_endpoint)
+                .json(&settings)
+                .bearer_auth(access_token)
+        },
+        true,
+    )?;
+    json_response(resp)
 }
@@ -0,0 +1,165 @@
+use std::fmt::{self, Formatter};
+use std::fs;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+use reqwest::{blocking, Certificate, Proxy, StatusCode};
+use serde::de::DeserializeOwned;
+
+use crate::managers::configs::AppConfigs;
+
+const DEFAULT_TIMEOUT: u64 = 20;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+static CLIENT: OnceLock<blocking::Client> = OnceLock::new();
+
+pub enum ApiError {
+    // the server could not be reached, punches are queued until it is back
+    Network(String),
+    // the token was refused, it expired or was revoked
+    Auth(String),
+    NotFound(String),
+    Conflict(String),
+    // anything else the server refused, e.g. invalid input
+    Rejected(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Network(message) => write!(f, "failed to reach the server: {}", message),
+            ApiError::Auth(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::Rejected(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+// every request goes through the one client, so the proxy and the certificates
+// of the profile apply to the calls to GitHub too
+pub fn configure_client(configs: &AppConfigs) -> Result<(), String> {
+    let mut builder = client_builder(configs.timeout.unwrap_or(DEFAULT_TIMEOUT));
+    if let Some(proxy) = &configs.proxy {
+        let proxy =
+            Proxy::all(proxy).map_err(|err| format!("invalid proxy '{}': {}", proxy, err))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &configs.ca_bundle {
+        let pem =
+            fs::read_to_string(path).map_err(|err| format!("failed to read {}: {}", path, err))?;
+        // a bundle holds several certificates, each is parsed on its own
+        let blocks: Vec<&str> = pem
+            .split_inclusive("-----END CERTIFICATE-----")
+            .filter(|block| block.contains("-----BEGIN CERTIFICATE-----"))
+            .collect();
+        if blocks.is_empty() {
+            return Err(format!("no certificates found in {}", path));
+        }
+        for block in blocks {
+            let certificate = Certificate::from_pem(block.as_bytes())
+                .map_err(|err| format!("failed to parse {}: {}", path, err))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    let client = builder
+        .build()
+        .map_err(|err| format!("failed to set up the http client: {}", err))?;
+    let _ = CLIENT.set(client);
+    Ok(())
+}
+
+// commands that run before the configuration is read get the defaults
+pub fn client() -> &'static blocking::Client {
+    CLIENT.get_or_init(|| {
+        client_builder(DEFAULT_TIMEOUT)
+            .build()
+            .expect("failed to set up the http client")
+    })
+}
+
+fn client_builder(timeout: u64) -> blocking::ClientBuilder {
+    blocking::Client::builder()
+        .user_agent(format!("punch-cli/{}", env!("CARGO_PKG_VERSION")))
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(Duration::from_secs(timeout))
+}
+
+// idempotent requests are sent again, with a growing pause, when they fail
+// once the connection is made. a refused connection means the server is down,
+// the punches are queued right away instead of waiting on it, and so are the
+// ones a gateway still could not hand over after the last attempt.
+pub fn send<F>(build: F, idempotent: bool) -> Result<blocking::Response, ApiError>
+where
+    F: Fn(&blocking::Client) -> blocking::RequestBuilder,
+{
+    let mut attempt = 1;
+    loop {
+        let result = build(client()).send();
+        let is_unavailable = matches!(&result, Ok(resp) if is_gateway_error(resp.status()));
+        let should_retry = idempotent
+            && attempt < MAX_ATTEMPTS
+            && match &result {
+                Ok(_) => is_unavailable,
+                Err(err) => !err.is_connect(),
+            };
+        if !should_retry {
+            return match result {
+                Ok(resp) if is_unavailable => Err(ApiError::Network(format!(
+                    "the server answered {}",
+                    resp.status()
+                ))),
+                result => result.map_err(|err| ApiError::Network(err.to_string())),
+            };
+        }
+        thread::sleep(RETRY_DELAY * 2u32.pow(attempt - 1));
+        attempt += 1;
+    }
+}
+
+fn is_gateway_error(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+pub fn json_response<T: DeserializeOwned>(resp: blocking::Response) -> Result<T, ApiError> {
+    if !resp.status().is_success() {
+        return Err(rejection(resp));
+    }
+    resp.json::<T>()
+        .map_err(|err| ApiError::Rejected(format!("failed to parse the response: {}", err)))
+}
+
+pub fn text_response(resp: blocking::Response) -> Result<String, ApiError> {
+    if !resp.status().is_success() {
+        return Err(rejection(resp));
+    }
+    resp.text()
+        .map_err(|err| ApiError::Rejected(format!("failed to parse the response: {}", err)))
+}
+
+pub fn empty_response(resp: blocking::Response) -> Result<(), ApiError> {
+    if !resp.status().is_success() {
+        return Err(rejection(resp));
+    }
+    Ok(())
+}
+
+// the body of an error response is the message of the server
+fn rejection(resp: blocking::Response) -> ApiError {
+    let status = resp.status();
+    let message = resp
+        .text()
+        .unwrap_or_else(|err| format!("failed to parse the response: {}", err));
+    match status {
+        StatusCode::UNAUTHORIZED => ApiError::Auth(message),
+        StatusCode::NOT_FOUND => ApiError::NotFound(message),
+        StatusCode::CONFLICT => ApiError::Conflict(message),
+        _ => ApiError::Rejected(message),
+    }
+}
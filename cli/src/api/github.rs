@@ -2,6 +2,9 @@ use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
 use std::{thread, time};
 
+use super::client::{send, text_response, ApiError};
+use crate::utils::printer::write_progress_message;
+
 #[derive(Deserialize, Serialize)]
 pub struct LoginPayload {
     pub client_id: String,
@@ -40,44 +43,48 @@ pub struct TokenResponse {
     scope: String,
 }
 
-pub fn fetch_gh_client_id(api_endpoint: &str, scope: &str) -> LoginPayload {
-    let res = reqwest::blocking::get(format!("{}/auth/client_id", api_endpoint))
-        .expect("failed to fetch client id from the server");
-    let client_id = res
-        .text()
-        .expect("failed to parse client id from the response");
-    LoginPayload {
+pub fn fetch_gh_client_id(api_endpoint: &str, scope: &str) -> Result<LoginPayload, ApiError> {
+    let endpoint = format!("{}/auth/client_id", api_endpoint);
+    let client_id = text_response(send(|client| client.get(&endpoint), true)?)?;
+    Ok(LoginPayload {
         client_id,
         scope: scope.to_string(),
-    }
+    })
 }
 
-pub fn fetch_gh_login_info(payload: &LoginPayload) -> LoginResponse {
-    let res = reqwest::blocking::Client::new()
-        .post("https://github.com/login/device/code")
-        .header("Accept", "application/json")
-        .json(&payload)
-        .send()
-        .expect("failed to attempt to login to GitHub");
+pub fn fetch_gh_login_info(payload: &LoginPayload) -> Result<LoginResponse, ApiError> {
+    let res = send(
+        |client| {
+            client
+                .post("https://github.com/login/device/code")
+                .header("Accept", "application/json")
+                .json(&payload)
+        },
+        false,
+    )?;
     match res.json::<GHLoginPayload>() {
-        Ok(parsed) => LoginResponse {
+        Ok(parsed) => Ok(LoginResponse {
             device_code: parsed.device_code,
             expires_in: parsed.expires_in,
             interval: parsed.interval,
             user_code: parsed.user_code,
             verification_uri: parsed.verification_uri,
-        },
-        Err(err) => {
-            panic!("failed to attempt to login to GitHub: {:?}", err);
-        }
+        }),
+        Err(err) => Err(ApiError::Rejected(format!(
+            "failed to attempt to login to GitHub: {}",
+            err
+        ))),
     }
 }
 
 pub fn prompt_and_fetch_gh_tokens(
     client_id_info: &LoginPayload,
     login_info: &LoginResponse,
-) -> TokenResponse {
-    println!("please enter your one-time code: {}", &login_info.user_code,);
+) -> Result<TokenResponse, ApiError> {
+    write_progress_message(format!(
+        "please enter your one-time code: {}",
+        &login_info.user_code
+    ));
     _ = open::that(login_info.verification_uri.clone());
     let auth_payload = AccessTokenPayload {
         client_id: client_id_info.client_id.clone(),
@@ -88,17 +95,22 @@ pub fn prompt_and_fetch_gh_tokens(
     let deadline = Utc::now() + Duration::seconds(login_info.expires_in as i64);
     loop {
         if Utc::now() > deadline {
-            panic!("failed to fetch tokens from github API");
+            return Err(ApiError::Rejected(String::from(
+                "the one-time code expired before it was entered",
+            )));
         }
-        let res = reqwest::blocking::Client::new()
-            .post("https://github.com/login/oauth/access_token")
-            .header("Accept", "application/json")
-            .json(&auth_payload)
-            .send()
-            .expect("failed to fetch tokens from github API");
+        let res = send(
+            |client| {
+                client
+                    .post("https://github.com/login/oauth/access_token")
+                    .header("Accept", "application/json")
+                    .json(&auth_payload)
+            },
+            false,
+        )?;
         match res.json::<TokenResponse>() {
             Ok(body) => {
-                return body;
+                return Ok(body);
             }
             Err(_) => {
                 thread::sleep(interval);
@@ -0,0 +1,358 @@
+use std::cmp;
+
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+use super::schema::{task_aggregates, task_tags, tasks};
+use super::sealed::sealed;
+use super::tag::get_task_tags;
+use super::task::Task;
+use crate::utils::report::ReportPeriod;
+
+// the time spent on a task during one local day, with the tags its sessions
+// were punched in with. started_at and finished_at are those of the sessions
+// within the day, the ones not uploaded yet are totalled on their own.
+#[derive(Queryable)]
+pub struct TaskAggregate {
+    pub name: String,
+    pub day: i64,
+    pub tags: String,
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub duration: i64,
+    pub sessions: i64,
+    pub pomodoros: i64,
+    pub uploaded: bool,
+}
+
+impl TaskAggregate {
+    pub fn tag_list(&self) -> Vec<String> {
+        self.tags
+            .split(',')
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| tag.to_owned())
+            .collect()
+    }
+
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        let own_tags = self.tag_list();
+        tags.iter().all(|tag| own_tags.contains(tag))
+    }
+
+    // the minutes of a day are no longer known, the time is counted from
+    // its start. reports by day, week or month add up the same as before.
+    pub fn as_session(&self) -> (String, i64, i64) {
+        (self.name.to_owned(), self.day, self.day + self.duration)
+    }
+}
+
+pub struct CompactReport {
+    pub sessions: usize,
+    pub aggregates: usize,
+}
+
+// rolls the sessions finished before the given time into daily totals,
+// sessions crossing midnight are split between the two days
+pub fn compact_sessions(before: i64, conn: &SqliteConnection) -> QueryResult<CompactReport> {
+    let mut report = CompactReport {
+        sessions: 0,
+        aggregates: 0,
+    };
+    conn.transaction(|| {
+        // the ones finished before they started are left for punch db repair
+        let sessions = tasks::table
+            .filter(tasks::finished_at.lt(before))
+            .filter(tasks::started_at.nullable().le(tasks::finished_at))
+            .order((tasks::started_at.asc(), tasks::id.asc()))
+            .load::<Task>(conn)?;
+        for session in sessions.iter() {
+            let tags = get_task_tags(session.id, conn)?.join(",");
+            let session_end = session.finished_at.unwrap();
+            let mut piece_start = session.started_at;
+            let mut is_first = true;
+            loop {
+                let day = ReportPeriod::Day.start_of(piece_start);
+                let piece_end = cmp::min(session_end, ReportPeriod::Day.next_start(day));
+                let piece = TaskAggregate {
+                    name: session.name.to_owned(),
+                    day,
+                    tags: tags.to_owned(),
+                    started_at: piece_start,
+                    finished_at: piece_end,
+                    duration: piece_end - piece_start,
+                    sessions: is_first as i64,
+                    pomodoros: (is_first && session.pomodoro) as i64,
+                    uploaded: session.uploaded,
+                };
+                if add_to_aggregate(&piece, conn)? {
+                    report.aggregates += 1;
+                }
+                is_first = false;
+                piece_start = piece_end;
+                if piece_start >= session_end {
+                    break;
+                }
+            }
+        }
+        let compacted = tasks::table
+            .filter(tasks::finished_at.lt(before))
+            .filter(tasks::started_at.nullable().le(tasks::finished_at));
+        diesel::delete(
+            task_tags::table.filter(task_tags::task_id.eq_any(compacted.select(tasks::id))),
+        )
+        .execute(conn)?;
+        diesel::delete(compacted).execute(conn)?;
+        report.sessions = sessions.len();
+        Ok(report)
+    })
+}
+
+// true when a new day was started for the task
+fn add_to_aggregate(piece: &TaskAggregate, conn: &SqliteConnection) -> QueryResult<bool> {
+    let key = (
+        sealed(&piece.name),
+        piece.day,
+        sealed(&piece.tags),
+        piece.uploaded,
+    );
+    match task_aggregates::table
+        .find(key.to_owned())
+        .first::<TaskAggregate>(conn)
+        .optional()?
+    {
+        Some(aggregate) => {
+            diesel::update(task_aggregates::table.find(key))
+                .set((
+                    task_aggregates::started_at
+                        .eq(cmp::max(aggregate.started_at, piece.started_at)),
+                    task_aggregates::finished_at
+                        .eq(cmp::max(aggregate.finished_at, piece.finished_at)),
+                    task_aggregates::duration.eq(aggregate.duration + piece.duration),
+                    task_aggregates::sessions.eq(aggregate.sessions + piece.sessions),
+                    task_aggregates::pomodoros.eq(aggregate.pomodoros + piece.pomodoros),
+                ))
+                .execute(conn)?;
+            Ok(false)
+        }
+        None => {
+            diesel::insert_into(task_aggregates::table)
+                .values((
+                    task_aggregates::name.eq(sealed(&piece.name)),
+                    task_aggregates::day.eq(piece.day),
+                    task_aggregates::tags.eq(sealed(&piece.tags)),
+                    task_aggregates::started_at.eq(piece.started_at),
+                    task_aggregates::finished_at.eq(piece.finished_at),
+                    task_aggregates::duration.eq(piece.duration),
+                    task_aggregates::sessions.eq(piece.sessions),
+                    task_aggregates::pomodoros.eq(piece.pomodoros),
+                    task_aggregates::uploaded.eq(piece.uploaded),
+                ))
+                .execute(conn)?;
+            Ok(true)
+        }
+    }
+}
+
+// the days whose time, as counted by as_session, falls between since and
+// until. the tasks under a prefix are picked by the caller.
+pub fn get_task_aggregates(
+    task_name: Option<&str>,
+    since: i64,
+    until: i64,
+    conn: &SqliteConnection,
+) -> QueryResult<Vec<TaskAggregate>> {
+    let mut query = task_aggregates::table
+        .filter((task_aggregates::day + task_aggregates::duration).ge(since))
+        .filter(task_aggregates::day.le(until))
+        .order(task_aggregates::day.asc())
+        .into_boxed();
+    if let Some(task_name) = task_name {
+        query = query.filter(task_aggregates::name.eq(sealed(task_name)));
+    }
+    let mut aggregates = query.load::<TaskAggregate>(conn)?;
+    aggregates.sort_by(|a, b| (a.day, &a.name).cmp(&(b.day, &b.name)));
+    Ok(aggregates)
+}
+
+// the days compacted from sessions recorded while logged out
+pub fn get_aggregates_to_upload(conn: &SqliteConnection) -> QueryResult<Vec<TaskAggregate>> {
+    let mut aggregates = task_aggregates::table
+        .filter(task_aggregates::uploaded.eq(false))
+        .load::<TaskAggregate>(conn)?;
+    aggregates.sort_by(|a, b| (a.day, &a.name, &a.tags).cmp(&(b.day, &b.name, &b.tags)));
+    Ok(aggregates)
+}
+
+// the days are added up with the ones already uploaded
+pub fn mark_aggregates_uploaded(
+    aggregates: &[TaskAggregate],
+    conn: &SqliteConnection,
+) -> QueryResult<()> {
+    conn.transaction(|| {
+        for aggregate in aggregates.iter().filter(|aggregate| !aggregate.uploaded) {
+            diesel::delete(task_aggregates::table.find((
+                sealed(&aggregate.name),
+                aggregate.day,
+                sealed(&aggregate.tags),
+                false,
+            )))
+            .execute(conn)?;
+            add_to_aggregate(
+                &TaskAggregate {
+                    name: aggregate.name.to_owned(),
+                    tags: aggregate.tags.to_owned(),
+                    uploaded: true,
+                    ..*aggregate
+                },
+                conn,
+            )?;
+        }
+        Ok(())
+    })
+}
+
+pub fn count_aggregates(conn: &SqliteConnection) -> QueryResult<i64> {
+    task_aggregates::table.count().get_result(conn)
+}
+
+pub fn get_aggregate_task_names(conn: &SqliteConnection) -> QueryResult<Vec<String>> {
+    let mut names = task_aggregates::table
+        .select(task_aggregates::name)
+        .distinct()
+        .load::<String>(conn)?;
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::database::test_connection;
+    use crate::database::tag::add_task_tags;
+    use crate::database::task::NewTask;
+
+    fn add_session(
+        task_name: &str,
+        from_ts: i64,
+        to_ts: i64,
+        tags: &[String],
+        uploaded: bool,
+        conn: &SqliteConnection,
+    ) {
+        NewTask {
+            name: task_name.to_owned(),
+            started_at: from_ts,
+            finished_at: Some(to_ts),
+            uploaded,
+            note: None,
+        }
+        .insert(conn)
+        .unwrap();
+        let task_id = tasks::table
+            .order(tasks::id.desc())
+            .select(tasks::id)
+            .first::<i32>(conn)
+            .unwrap();
+        add_task_tags(task_id, tags, conn).unwrap();
+    }
+
+    #[test]
+    fn sessions_crossing_midnight_are_split_between_the_days() {
+        let conn = test_connection();
+        let first_day = ReportPeriod::Day.start_of(1_600_000_000);
+        let midnight = ReportPeriod::Day.next_start(first_day);
+        let tags = vec![String::from("work")];
+        add_session(
+            "alpha",
+            first_day + 3600,
+            first_day + 7200,
+            &tags,
+            true,
+            &conn,
+        );
+        add_session(
+            "alpha",
+            midnight - 3600,
+            midnight + 1800,
+            &tags,
+            true,
+            &conn,
+        );
+
+        let report = compact_sessions(ReportPeriod::Day.next_start(midnight), &conn).unwrap();
+        assert_eq!(report.sessions, 2);
+        assert_eq!(report.aggregates, 2);
+
+        let aggregates = get_task_aggregates(Some("alpha"), 0, i64::MAX, &conn).unwrap();
+        assert_eq!(aggregates.len(), 2);
+        assert_eq!(aggregates[0].day, first_day);
+        assert_eq!(aggregates[0].duration, 7200);
+        assert_eq!(aggregates[0].sessions, 2);
+        assert_eq!(aggregates[0].finished_at, midnight);
+        assert_eq!(aggregates[1].day, midnight);
+        assert_eq!(aggregates[1].duration, 1800);
+        assert_eq!(aggregates[1].sessions, 0);
+        assert_eq!(aggregates[1].tag_list(), tags);
+        assert_eq!(tasks::table.count().get_result::<i64>(&conn).unwrap(), 0);
+        assert_eq!(
+            task_tags::table.count().get_result::<i64>(&conn).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn sessions_not_uploaded_are_kept_apart_until_uploaded() {
+        let conn = test_connection();
+        let first_day = ReportPeriod::Day.start_of(1_600_000_000);
+        let tags = vec![String::from("work")];
+        add_session(
+            "alpha",
+            first_day + 60,
+            first_day + 120,
+            &tags,
+            false,
+            &conn,
+        );
+        add_session(
+            "alpha",
+            first_day + 600,
+            first_day + 900,
+            &tags,
+            false,
+            &conn,
+        );
+        add_session(
+            "alpha",
+            first_day + 3600,
+            first_day + 4800,
+            &tags,
+            true,
+            &conn,
+        );
+
+        let report = compact_sessions(ReportPeriod::Day.next_start(first_day), &conn).unwrap();
+        assert_eq!(report.sessions, 3);
+        assert_eq!(report.aggregates, 2);
+        assert_eq!(tasks::table.count().get_result::<i64>(&conn).unwrap(), 0);
+
+        let to_upload = get_aggregates_to_upload(&conn).unwrap();
+        assert_eq!(to_upload.len(), 1);
+        assert_eq!(to_upload[0].duration, 360);
+        assert_eq!(to_upload[0].sessions, 2);
+        let total: i64 = get_task_aggregates(Some("alpha"), 0, i64::MAX, &conn)
+            .unwrap()
+            .iter()
+            .map(|aggregate| aggregate.duration)
+            .sum();
+        assert_eq!(total, 1560);
+
+        mark_aggregates_uploaded(&to_upload, &conn).unwrap();
+        assert!(get_aggregates_to_upload(&conn).unwrap().is_empty());
+        let aggregates = get_task_aggregates(Some("alpha"), 0, i64::MAX, &conn).unwrap();
+        assert_eq!(aggregates.len(), 1);
+        assert!(aggregates[0].uploaded);
+        assert_eq!(aggregates[0].duration, 1560);
+        assert_eq!(aggregates[0].sessions, 3);
+        assert_eq!(aggregates[0].tag_list(), tags);
+    }
+}
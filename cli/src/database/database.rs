@@ -1,7 +1,47 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
 
-pub fn create_connection() -> Result<SqliteConnection, std::io::Error> {
-    Ok(SqliteConnection::establish("./punchcard.db")
-        .expect("failed to establish database connection"))
+use crate::managers::configs::DEFAULT_PROFILE;
+
+pub const DATABASE_FILE_NAME: &str = "punchcard.db";
+
+// $XDG_DATA_HOME/punch-cli/punchcard.db on linux, so the history is the same
+// whichever directory punch runs from. the other profiles get a database of
+// their own under profiles/, the sessions of two servers never mix.
+pub fn default_database_path(profile: &str) -> Result<PathBuf, String> {
+    let dir = match dirs::data_dir() {
+        Some(dir) => dir.join("punch-cli"),
+        None => return Err(String::from("failed to find the data directory")),
+    };
+    if profile == DEFAULT_PROFILE {
+        Ok(dir.join(DATABASE_FILE_NAME))
+    } else {
+        Ok(dir.join("profiles").join(profile).join(DATABASE_FILE_NAME))
+    }
+}
+
+pub fn create_connection(path: &Path) -> Result<SqliteConnection, String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("failed to create {}: {}", dir.display(), err))?;
+    }
+    let conn = SqliteConnection::establish(&path.to_string_lossy())
+        .map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+    // sqlite checks foreign keys only when asked, the tags of a deleted
+    // session go with it from here on
+    conn.execute("PRAGMA foreign_keys = ON")
+        .map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+    Ok(conn)
+}
+
+// a fresh database for the tests, migrated like the ones on disk
+#[cfg(test)]
+pub fn test_connection() -> SqliteConnection {
+    let conn = SqliteConnection::establish(":memory:").unwrap();
+    conn.execute("PRAGMA foreign_keys = ON").unwrap();
+    super::migrations::run_migrations(&conn).unwrap();
+    conn
 }
@@ -0,0 +1,109 @@
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::Text;
+use diesel::sqlite::SqliteConnection;
+
+use super::schema::tasks;
+use super::task::Task;
+
+#[derive(QueryableByName)]
+struct IntegrityRow {
+    #[sql_type = "Text"]
+    integrity_check: String,
+}
+
+pub struct DatabaseProblems {
+    // what sqlite itself found wrong with the file
+    pub integrity: Vec<String>,
+    pub reversed_sessions: Vec<Task>,
+    // every open session of a task that is in progress more than once
+    pub duplicate_open_sessions: Vec<Task>,
+}
+
+impl DatabaseProblems {
+    pub fn is_empty(&self) -> bool {
+        self.integrity.is_empty()
+            && self.reversed_sessions.is_empty()
+            && self.duplicate_open_sessions.is_empty()
+    }
+}
+
+pub fn find_problems(conn: &SqliteConnection) -> QueryResult<DatabaseProblems> {
+    let integrity = sql_query("PRAGMA integrity_check")
+        .load::<IntegrityRow>(conn)?
+        .into_iter()
+        .map(|row| row.integrity_check)
+        .filter(|message| message != "ok")
+        .collect();
+    Ok(DatabaseProblems {
+        integrity,
+        reversed_sessions: get_reversed_sessions(conn)?,
+        duplicate_open_sessions: get_duplicate_open_sessions(conn)?,
+    })
+}
+
+fn get_reversed_sessions(conn: &SqliteConnection) -> QueryResult<Vec<Task>> {
+    tasks::table
+        .filter(tasks::started_at.nullable().gt(tasks::finished_at))
+        .order((tasks::started_at.asc(), tasks::id.asc()))
+        .load::<Task>(conn)
+}
+
+fn get_duplicate_open_sessions(conn: &SqliteConnection) -> QueryResult<Vec<Task>> {
+    let mut open = tasks::table
+        .filter(tasks::finished_at.is_null())
+        .order((tasks::started_at.asc(), tasks::id.asc()))
+        .load::<Task>(conn)?;
+    // the sessions of a task follow each other, the sort is stable
+    open.sort_by(|a, b| a.name.cmp(&b.name));
+    let count = |task_name: &str| open.iter().filter(|task| task.name == task_name).count();
+    let duplicates = open
+        .iter()
+        .filter(|task| count(&task.name) > 1)
+        .map(|task| task.id)
+        .collect::<Vec<i32>>();
+    Ok(open
+        .into_iter()
+        .filter(|task| duplicates.contains(&task.id))
+        .collect())
+}
+
+// the times were most likely entered the wrong way around, the length of the
+// session is kept
+pub fn swap_reversed_sessions(conn: &SqliteConnection) -> QueryResult<usize> {
+    let sessions = get_reversed_sessions(conn)?;
+    conn.transaction(|| {
+        for session in sessions.iter() {
+            diesel::update(tasks::table.find(session.id))
+                .set((
+                    tasks::started_at.eq(session.finished_at.unwrap_or(session.started_at)),
+                    tasks::finished_at.eq(Some(session.started_at)),
+                ))
+                .execute(conn)?;
+        }
+        Ok(sessions.len())
+    })
+}
+
+// the task was punched in again without a punch out, so every open session
+// but the newest is finished when the next one started
+pub fn finish_duplicate_open_sessions(conn: &SqliteConnection) -> QueryResult<usize> {
+    let sessions = get_duplicate_open_sessions(conn)?;
+    let mut finished = 0;
+    conn.transaction(|| {
+        for (session, next) in sessions.iter().zip(sessions.iter().skip(1)) {
+            if session.name != next.name {
+                continue;
+            }
+            diesel::update(tasks::table.find(session.id))
+                .set(tasks::finished_at.eq(Some(next.started_at)))
+                .execute(conn)?;
+            finished += 1;
+        }
+        Ok(finished)
+    })
+}
+
+pub fn vacuum(conn: &SqliteConnection) -> QueryResult<()> {
+    conn.execute("VACUUM").map(|_| ())
+}
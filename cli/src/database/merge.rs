@@ -0,0 +1,78 @@
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+use super::schema::tasks;
+use super::sync::{get_pending_operations, queue_operation, NewPendingOperation};
+use super::tag::{add_task_tags, get_task_tags};
+use super::task::{get_unfinished_task, session_exists, NewTask, Task};
+
+pub struct MergeReport {
+    pub sessions: usize,
+    pub skipped: usize,
+    pub operations: usize,
+}
+
+pub fn count_sessions(conn: &SqliteConnection) -> QueryResult<i64> {
+    tasks::table.count().get_result(conn)
+}
+
+// copies the sessions and the punches waiting for a sync from another
+// database. sessions with the same task and start are already there, and a
+// task cannot be in progress twice.
+pub fn merge_database(
+    source: &SqliteConnection,
+    target: &SqliteConnection,
+) -> QueryResult<MergeReport> {
+    let mut report = MergeReport {
+        sessions: 0,
+        skipped: 0,
+        operations: 0,
+    };
+    let sessions = tasks::table.order(tasks::id.asc()).load::<Task>(source)?;
+    let operations = get_pending_operations(source)?;
+    target.transaction(|| {
+        for session in sessions {
+            if session_exists(&session.name, session.started_at, target)?
+                || (session.finished_at.is_none()
+                    && !get_unfinished_task(&session.name, target).is_empty())
+            {
+                report.skipped += 1;
+                continue;
+            }
+            NewTask {
+                name: session.name.to_owned(),
+                started_at: session.started_at,
+                finished_at: session.finished_at,
+                uploaded: session.uploaded,
+                note: session.note.to_owned(),
+            }
+            .insert(target)?;
+            let inserted = tasks::table
+                .order(tasks::id.desc())
+                .select(tasks::id)
+                .first::<i32>(target)?;
+            diesel::update(tasks::table.find(inserted))
+                .set(tasks::pomodoro.eq(session.pomodoro))
+                .execute(target)?;
+            add_task_tags(inserted, &get_task_tags(session.id, source)?, target)?;
+            report.sessions += 1;
+        }
+        for operation in operations {
+            queue_operation(
+                &NewPendingOperation {
+                    kind: operation.kind,
+                    task_name: operation.task_name,
+                    timestamp: operation.timestamp,
+                    tags: operation.tags,
+                    note: operation.note,
+                    finished_at: operation.finished_at,
+                    force: operation.force,
+                    pomodoro: operation.pomodoro,
+                },
+                target,
+            )?;
+            report.operations += 1;
+        }
+        Ok(report)
+    })
+}
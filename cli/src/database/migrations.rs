@@ -0,0 +1,72 @@
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Text};
+use diesel::sqlite::SqliteConnection;
+
+embed_migrations!("./migrations");
+
+#[derive(QueryableByName)]
+struct MigrationVersion {
+    #[sql_type = "Text"]
+    version: String,
+}
+
+// returns the versions of the migrations that were applied
+pub fn run_migrations(conn: &SqliteConnection) -> Result<Vec<String>, String> {
+    let mut output = vec![];
+    embedded_migrations::run_with_output(conn, &mut output).map_err(|err| err.to_string())?;
+    Ok(String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| line.strip_prefix("Running migration "))
+        .map(|version| version.to_owned())
+        .collect())
+}
+
+// diesel keeps the list of embedded migrations to itself, so they are run in
+// a transaction that is rolled back to learn which ones are missing
+pub fn pending_migrations(conn: &SqliteConnection) -> Result<Vec<String>, String> {
+    let mut pending = Ok(vec![]);
+    let _ = conn.transaction::<(), diesel::result::Error, _>(|| {
+        pending = run_migrations(conn);
+        Err(diesel::result::Error::RollbackTransaction)
+    });
+    pending
+}
+
+// the version of the newest migration applied, none for a new database
+pub fn schema_version(conn: &SqliteConnection) -> Result<Option<String>, String> {
+    let tables = sql_query(
+        "SELECT name AS version FROM sqlite_master \
+         WHERE type = 'table' AND name = '__diesel_schema_migrations'",
+    )
+    .load::<MigrationVersion>(conn)
+    .map_err(|err| err.to_string())?;
+    if tables.is_empty() {
+        return Ok(None);
+    }
+    sql_query("SELECT version FROM __diesel_schema_migrations ORDER BY version DESC LIMIT 1")
+        .load::<MigrationVersion>(conn)
+        .map(|versions| versions.into_iter().next().map(|row| row.version))
+        .map_err(|err| err.to_string())
+}
+
+#[derive(QueryableByName)]
+pub struct AppliedMigration {
+    #[sql_type = "Text"]
+    pub version: String,
+    // seconds since the epoch
+    #[sql_type = "BigInt"]
+    pub run_on: i64,
+}
+
+pub fn applied_migrations(conn: &SqliteConnection) -> Result<Vec<AppliedMigration>, String> {
+    if schema_version(conn)?.is_none() {
+        return Ok(vec![]);
+    }
+    sql_query(
+        "SELECT version, CAST(strftime('%s', run_on) AS INTEGER) AS run_on \
+         FROM __diesel_schema_migrations ORDER BY version",
+    )
+    .load::<AppliedMigration>(conn)
+    .map_err(|err| err.to_string())
+}
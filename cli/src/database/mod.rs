@@ -1,3 +1,11 @@
+pub mod aggregate;
 pub mod database;
+pub mod maintenance;
+pub mod merge;
+pub mod migrations;
 pub mod schema;
+pub mod sealed;
+pub mod settings;
+pub mod sync;
+pub mod tag;
 pub mod task;
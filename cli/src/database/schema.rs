@@ -1,8 +1,82 @@
 table! {
+    use diesel::sql_types::*;
+    use crate::database::sealed::Sealed;
+
+    cached_tasks (name) {
+        name -> Sealed,
+        duration -> BigInt,
+        started_at -> BigInt,
+        finished_at -> Nullable<BigInt>,
+        pomodoros -> BigInt,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::database::sealed::Sealed;
+
+    pending_operations (id) {
+        id -> Integer,
+        kind -> Text,
+        task_name -> Sealed,
+        timestamp -> BigInt,
+        tags -> Sealed,
+        note -> Nullable<Sealed>,
+        finished_at -> Nullable<BigInt>,
+        force -> Bool,
+        pomodoro -> Bool,
+    }
+}
+
+table! {
+    settings (key) {
+        key -> Text,
+        value -> Text,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::database::sealed::Sealed;
+
+    task_aggregates (name, day, tags, uploaded) {
+        name -> Sealed,
+        day -> BigInt,
+        tags -> Sealed,
+        started_at -> BigInt,
+        finished_at -> BigInt,
+        duration -> BigInt,
+        sessions -> BigInt,
+        pomodoros -> BigInt,
+        uploaded -> Bool,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::database::sealed::Sealed;
+
+    task_tags (task_id, tag) {
+        task_id -> Integer,
+        tag -> Sealed,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::database::sealed::Sealed;
+
     tasks (id) {
         id -> Integer,
-        name -> Text,
+        name -> Sealed,
         started_at -> BigInt,
         finished_at -> Nullable<BigInt>,
+        uploaded -> Bool,
+        note -> Nullable<Sealed>,
+        pomodoro -> Bool,
     }
 }
+
+joinable!(task_tags -> tasks (task_id));
+
+allow_tables_to_appear_in_same_query!(task_tags, tasks);
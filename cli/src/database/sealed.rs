@@ -0,0 +1,266 @@
+use std::io::Write;
+use std::sync::OnceLock;
+
+use aes_gcm_siv::aead::{Aead, KeyInit, OsRng};
+use aes_gcm_siv::{Aes256GcmSiv, Nonce};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql};
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Text};
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use keyring::Error as KeyringError;
+
+use super::settings::{get_setting, remove_setting, set_setting, DATABASE_KEY_CHECK};
+use crate::managers::keyring::SecretsManager;
+
+const SEALED_PREFIX: &str = "enc1:";
+// sealed with the key to tell whether the keyring holds the right one
+const KEY_CHECK: &str = "punchcard";
+// the columns of the Sealed type in schema.rs
+const SEALED_COLUMNS: [(&str, &str); 9] = [
+    ("tasks", "name"),
+    ("tasks", "note"),
+    ("task_tags", "tag"),
+    ("task_aggregates", "name"),
+    ("task_aggregates", "tags"),
+    ("cached_tasks", "name"),
+    ("pending_operations", "task_name"),
+    ("pending_operations", "tags"),
+    ("pending_operations", "note"),
+];
+
+static CIPHER: OnceLock<DatabaseCipher> = OnceLock::new();
+
+// text that is encrypted once the database is. the names, notes and tags are
+// the same text as before for diesel, the encryption happens when they are
+// bound to a query or read from a row.
+#[derive(SqlType, QueryId)]
+#[sqlite_type = "Text"]
+pub struct Sealed;
+
+// aes-gcm-siv with a fixed nonce always seals a name the same way, which is
+// what lets sqlite still filter, join and group by it. the only thing it
+// gives away is which rows share a name.
+pub struct DatabaseCipher(Aes256GcmSiv);
+
+impl DatabaseCipher {
+    pub fn from_key(key: &str) -> Result<DatabaseCipher, String> {
+        let key = URL_SAFE_NO_PAD
+            .decode(key)
+            .ok()
+            .and_then(|key| Aes256GcmSiv::new_from_slice(&key).ok())
+            .ok_or_else(|| String::from("the database key in the keyring is malformed"))?;
+        Ok(DatabaseCipher(key))
+    }
+
+    pub fn generate_key() -> String {
+        URL_SAFE_NO_PAD.encode(Aes256GcmSiv::generate_key(&mut OsRng))
+    }
+
+    pub fn seal(&self, value: &str) -> String {
+        let sealed = self
+            .0
+            .encrypt(Nonce::from_slice(&[0; 12]), value.as_bytes())
+            .expect("failed to encrypt");
+        format!("{}{}", SEALED_PREFIX, URL_SAFE_NO_PAD.encode(sealed))
+    }
+
+    // plain text, e.g. from an unencrypted database being merged, comes back
+    // as it is. an encrypted database has every value sealed, even the ones
+    // that happen to start with the prefix.
+    pub fn open(&self, value: &str) -> Result<String, String> {
+        let sealed = match value.strip_prefix(SEALED_PREFIX) {
+            Some(sealed) => sealed,
+            None => return Ok(value.to_owned()),
+        };
+        URL_SAFE_NO_PAD
+            .decode(sealed)
+            .ok()
+            .and_then(|sealed| {
+                self.0
+                    .decrypt(Nonce::from_slice(&[0; 12]), sealed.as_slice())
+                    .ok()
+            })
+            .and_then(|opened| String::from_utf8(opened).ok())
+            .ok_or_else(|| String::from("failed to decrypt the database, the key does not match"))
+    }
+}
+
+pub fn is_encrypted(conn: &SqliteConnection) -> bool {
+    get_setting(DATABASE_KEY_CHECK, conn).is_some()
+}
+
+// loads the key of an encrypted database, every query from here on seals and
+// opens the text it handles
+pub fn unlock_database<T: SecretsManager>(conn: &SqliteConnection, sm: &T) -> Result<(), String> {
+    let check = match get_setting(DATABASE_KEY_CHECK, conn) {
+        Some(check) => check,
+        None => return Ok(()),
+    };
+    let key = sm.retrieve_secrets().map_err(|err| match err {
+        KeyringError::NoEntry => {
+            String::from("the database is encrypted and its key is missing from the keyring")
+        }
+        err => format!("failed to read the database key from the keyring: {}", err),
+    })?;
+    let cipher = DatabaseCipher::from_key(&key)?;
+    if cipher.open(&check)? != KEY_CHECK {
+        return Err(String::from(
+            "the key in the keyring does not open the database",
+        ));
+    }
+    let _ = CIPHER.set(cipher);
+    Ok(())
+}
+
+// the key of the profile is kept when the database is decrypted again, the
+// databases given with --db may still use it
+pub fn encrypt_database<T: SecretsManager>(conn: &SqliteConnection, sm: &T) -> Result<(), String> {
+    if is_encrypted(conn) {
+        return Err(String::from("the database is already encrypted"));
+    }
+    let key = match sm.retrieve_secrets() {
+        Ok(key) => key,
+        Err(KeyringError::NoEntry) => {
+            let key = DatabaseCipher::generate_key();
+            sm.save_secrets(&key);
+            key
+        }
+        Err(err) => return Err(format!("the keyring is not available: {}", err)),
+    };
+    let cipher = DatabaseCipher::from_key(&key)?;
+    convert_database(
+        conn,
+        |value| Ok(cipher.seal(value)),
+        Some(cipher.seal(KEY_CHECK)),
+    )?;
+    // the plain text stays in the free pages of the file until it is rebuilt
+    conn.execute("VACUUM").map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+pub fn decrypt_database(conn: &SqliteConnection) -> Result<(), String> {
+    let cipher = match CIPHER.get() {
+        Some(cipher) => cipher,
+        None => return Err(String::from("the database is not encrypted")),
+    };
+    convert_database(conn, |value| cipher.open(value), None)?;
+    conn.execute("VACUUM").map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct StoredText {
+    #[sql_type = "BigInt"]
+    id: i64,
+    #[sql_type = "Text"]
+    value: String,
+}
+
+// every value is converted before the first one is written, so that a value
+// that cannot be opened leaves the database as it was. the key check is
+// written with them, the database is never encrypted by halves.
+fn convert_database<F>(
+    conn: &SqliteConnection,
+    convert: F,
+    key_check: Option<String>,
+) -> Result<(), String>
+where
+    F: Fn(&str) -> Result<String, String>,
+{
+    let mut updates = vec![];
+    for (table, column) in SEALED_COLUMNS {
+        let rows = sql_query(format!(
+            "SELECT rowid AS id, {column} AS value FROM {table} WHERE {column} IS NOT NULL"
+        ))
+        .load::<StoredText>(conn)
+        .map_err(|err| err.to_string())?;
+        for row in rows {
+            let value = convert(&row.value)?;
+            if value != row.value {
+                updates.push((table, column, row.id, value));
+            }
+        }
+    }
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        for (table, column, id, value) in updates.iter() {
+            sql_query(format!("UPDATE {table} SET {column} = ? WHERE rowid = ?"))
+                .bind::<Text, _>(value)
+                .bind::<BigInt, _>(id)
+                .execute(conn)?;
+        }
+        match &key_check {
+            Some(check) => set_setting(DATABASE_KEY_CHECK, check, conn)?,
+            None => remove_setting(DATABASE_KEY_CHECK, conn)?,
+        };
+        Ok(())
+    })
+    .map_err(|err| err.to_string())
+}
+
+// for the text compared with sealed columns in hand written queries
+pub fn seal_text(value: &str) -> String {
+    match CIPHER.get() {
+        Some(cipher) => cipher.seal(value),
+        None => value.to_owned(),
+    }
+}
+
+impl ToSql<Sealed, Sqlite> for str {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+        ToSql::<Text, Sqlite>::to_sql(seal_text(self).as_str(), out)
+    }
+}
+
+impl ToSql<Sealed, Sqlite> for String {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+        ToSql::<Sealed, Sqlite>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Sealed, Sqlite> for String {
+    fn from_sql(value: Option<&<Sqlite as Backend>::RawValue>) -> deserialize::Result<Self> {
+        let text = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        match CIPHER.get() {
+            Some(cipher) => cipher.open(&text).map_err(|err| err.into()),
+            None => Ok(text),
+        }
+    }
+}
+
+// the text bound to a sealed column, diesel's own impls keep a String from
+// being bound to it directly
+#[derive(AsExpression, Clone, Debug)]
+#[sql_type = "Sealed"]
+pub struct SealedText(pub String);
+
+impl ToSql<Sealed, Sqlite> for SealedText {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+        ToSql::<Sealed, Sqlite>::to_sql(self.0.as_str(), out)
+    }
+}
+
+impl From<String> for SealedText {
+    fn from(value: String) -> SealedText {
+        SealedText(value)
+    }
+}
+
+impl From<&str> for SealedText {
+    fn from(value: &str) -> SealedText {
+        SealedText(value.to_owned())
+    }
+}
+
+impl From<&String> for SealedText {
+    fn from(value: &String) -> SealedText {
+        SealedText(value.to_owned())
+    }
+}
+
+pub fn sealed<T: Into<SealedText>>(value: T) -> SealedText {
+    value.into()
+}
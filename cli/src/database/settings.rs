@@ -0,0 +1,39 @@
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+use super::schema::settings;
+
+pub const TASK_POLICY: &str = "task_policy";
+// followed by the path of a database the user chose not to merge
+pub const IGNORED_DATABASE: &str = "ignored_database";
+// set while the database is encrypted
+pub const DATABASE_KEY_CHECK: &str = "database_key_check";
+
+#[derive(Insertable, Queryable)]
+#[table_name = "settings"]
+pub struct Setting {
+    pub key: String,
+    pub value: String,
+}
+
+pub fn get_setting(key: &str, conn: &SqliteConnection) -> Option<String> {
+    settings::table
+        .find(key)
+        .select(settings::value)
+        .first::<String>(conn)
+        .optional()
+        .unwrap_or(None)
+}
+
+pub fn set_setting(key: &str, value: &str, conn: &SqliteConnection) -> QueryResult<usize> {
+    diesel::replace_into(settings::table)
+        .values(&Setting {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+        .execute(conn)
+}
+
+pub fn remove_setting(key: &str, conn: &SqliteConnection) -> QueryResult<usize> {
+    diesel::delete(settings::table.find(key)).execute(conn)
+}
@@ -0,0 +1,196 @@
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+use super::schema::{cached_tasks, pending_operations};
+use super::sealed::sealed;
+
+pub const PUNCH_IN: &str = "in";
+pub const PUNCH_OUT: &str = "out";
+pub const PUNCH_CANCEL: &str = "cancel";
+pub const PUNCH_ADD: &str = "add";
+pub const PUNCH_SWITCH: &str = "switch";
+
+#[derive(Clone, Queryable)]
+pub struct PendingOperation {
+    pub id: i32,
+    pub kind: String,
+    pub task_name: String,
+    pub timestamp: i64,
+    pub tags: String,
+    pub note: Option<String>,
+    pub finished_at: Option<i64>,
+    pub force: bool,
+    pub pomodoro: bool,
+}
+
+pub struct NewPendingOperation {
+    pub kind: String,
+    pub task_name: String,
+    pub timestamp: i64,
+    pub tags: String,
+    pub note: Option<String>,
+    pub finished_at: Option<i64>,
+    pub force: bool,
+    pub pomodoro: bool,
+}
+
+#[derive(Queryable)]
+pub struct CachedTask {
+    pub name: String,
+    pub duration: i64,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+    pub pomodoros: i64,
+}
+
+pub fn queue_operation(
+    operation: &NewPendingOperation,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    diesel::insert_into(pending_operations::table)
+        .values((
+            pending_operations::kind.eq(&operation.kind),
+            pending_operations::task_name.eq(sealed(&operation.task_name)),
+            pending_operations::timestamp.eq(operation.timestamp),
+            pending_operations::tags.eq(sealed(&operation.tags)),
+            pending_operations::note.eq(operation.note.as_ref().map(sealed)),
+            pending_operations::finished_at.eq(operation.finished_at),
+            pending_operations::force.eq(operation.force),
+            pending_operations::pomodoro.eq(operation.pomodoro),
+        ))
+        .execute(conn)
+}
+
+pub fn get_pending_operations(conn: &SqliteConnection) -> QueryResult<Vec<PendingOperation>> {
+    pending_operations::table
+        .order(pending_operations::id.asc())
+        .load::<PendingOperation>(conn)
+}
+
+pub fn remove_pending_operation(operation_id: i32, conn: &SqliteConnection) -> QueryResult<usize> {
+    diesel::delete(pending_operations::table.find(operation_id)).execute(conn)
+}
+
+pub fn get_cached_task(task_name: &str, conn: &SqliteConnection) -> Option<CachedTask> {
+    cached_tasks::table
+        .find(sealed(task_name))
+        .first::<CachedTask>(conn)
+        .optional()
+        .unwrap_or(None)
+}
+
+pub fn get_cached_tasks(conn: &SqliteConnection) -> QueryResult<Vec<CachedTask>> {
+    let mut tasks = cached_tasks::table.load::<CachedTask>(conn)?;
+    tasks.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(tasks)
+}
+
+pub fn replace_cached_tasks(tasks: &[CachedTask], conn: &SqliteConnection) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(cached_tasks::table).execute(conn)?;
+        for task in tasks {
+            save_cached_task(task, conn)?;
+        }
+        Ok(())
+    })
+}
+
+// keeps the cached copy of the server data in line with a punch that was
+// recorded locally, so that it can still be listed while offline.
+pub fn apply_to_cache(
+    kind: &str,
+    task_name: &str,
+    timestamp: i64,
+    pomodoro: bool,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    let cached = get_cached_task(task_name, conn);
+    let updated = match (kind, cached) {
+        (PUNCH_IN, Some(task)) => CachedTask {
+            started_at: timestamp,
+            finished_at: None,
+            ..task
+        },
+        (PUNCH_IN, None) => CachedTask {
+            name: task_name.to_owned(),
+            duration: 0,
+            started_at: timestamp,
+            finished_at: None,
+            pomodoros: 0,
+        },
+        (PUNCH_OUT, Some(task)) if task.finished_at.is_none() => CachedTask {
+            duration: task.duration + (timestamp - task.started_at),
+            finished_at: Some(timestamp),
+            pomodoros: task.pomodoros + pomodoro as i64,
+            ..task
+        },
+        (PUNCH_CANCEL, Some(task)) if task.finished_at.is_none() => CachedTask {
+            finished_at: Some(task.started_at),
+            ..task
+        },
+        _ => return Ok(0),
+    };
+    save_cached_task(&updated, conn)
+}
+
+// a session entered after the fact only adds to the total, it never changes
+// whether the task is currently in progress.
+pub fn add_to_cache(
+    task_name: &str,
+    started_at: i64,
+    finished_at: i64,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    let updated = match get_cached_task(task_name, conn) {
+        Some(task) => CachedTask {
+            duration: task.duration + (finished_at - started_at),
+            ..task
+        },
+        None => CachedTask {
+            name: task_name.to_owned(),
+            duration: finished_at - started_at,
+            started_at,
+            finished_at: Some(finished_at),
+            pomodoros: 0,
+        },
+    };
+    save_cached_task(&updated, conn)
+}
+
+// swaps the time of an edited session in the total, the times shown for the
+// task are those of the session that started last.
+pub fn edit_in_cache(
+    task_name: &str,
+    before: (i64, Option<i64>),
+    after: (i64, Option<i64>),
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    let task = match get_cached_task(task_name, conn) {
+        Some(task) => task,
+        None => return Ok(0),
+    };
+    let length = |(started, finished): (i64, Option<i64>)| {
+        finished.map(|finished| finished - started).unwrap_or(0)
+    };
+    let is_latest = task.started_at == before.0;
+    let updated = CachedTask {
+        name: task.name,
+        duration: task.duration - length(before) + length(after),
+        started_at: if is_latest { after.0 } else { task.started_at },
+        finished_at: if is_latest { after.1 } else { task.finished_at },
+        pomodoros: task.pomodoros,
+    };
+    save_cached_task(&updated, conn)
+}
+
+fn save_cached_task(task: &CachedTask, conn: &SqliteConnection) -> QueryResult<usize> {
+    diesel::replace_into(cached_tasks::table)
+        .values((
+            cached_tasks::name.eq(sealed(&task.name)),
+            cached_tasks::duration.eq(task.duration),
+            cached_tasks::started_at.eq(task.started_at),
+            cached_tasks::finished_at.eq(task.finished_at),
+            cached_tasks::pomodoros.eq(task.pomodoros),
+        ))
+        .execute(conn)
+}
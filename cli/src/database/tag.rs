@@ -0,0 +1,54 @@
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+use super::schema::{task_tags, tasks};
+use super::sealed::sealed;
+
+pub fn add_task_tags(task_id: i32, tags: &[String], conn: &SqliteConnection) -> QueryResult<usize> {
+    let rows: Vec<_> = tags
+        .iter()
+        .map(|tag| {
+            (
+                task_tags::task_id.eq(task_id),
+                task_tags::tag.eq(sealed(tag)),
+            )
+        })
+        .collect();
+    diesel::insert_into(task_tags::table)
+        .values(&rows)
+        .execute(conn)
+}
+
+pub fn get_task_tags(task_id: i32, conn: &SqliteConnection) -> QueryResult<Vec<String>> {
+    let mut tags = task_tags::table
+        .filter(task_tags::task_id.eq(task_id))
+        .select(task_tags::tag)
+        .load::<String>(conn)?;
+    // sqlite would sort the tags of an encrypted database by their sealed text
+    tags.sort();
+    Ok(tags)
+}
+
+// pairs of task name and tag, used to show every tag a task was ever
+// punched in with.
+pub fn get_tags_by_task_name(conn: &SqliteConnection) -> QueryResult<Vec<(String, String)>> {
+    let mut pairs = task_tags::table
+        .inner_join(tasks::table)
+        .select((tasks::name, task_tags::tag))
+        .distinct()
+        .load::<(String, String)>(conn)?;
+    pairs.sort();
+    Ok(pairs)
+}
+
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = tags
+        .iter()
+        .flat_map(|tag| tag.split(','))
+        .map(|tag| tag.trim().trim_start_matches('+').to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
@@ -4,21 +4,40 @@ use diesel::prelude::*;
 use diesel::sql_types::{BigInt, Nullable};
 use diesel::sqlite::SqliteConnection;
 
-use super::schema::tasks::{self, finished_at, name};
+use super::schema::tasks::{self, finished_at, name, started_at, uploaded};
+use super::sealed::sealed;
 
-#[derive(Insertable, Queryable)]
+#[derive(Queryable)]
 pub struct Task {
     pub id: i32,
     pub name: String,
     pub started_at: i64,
     pub finished_at: Option<i64>,
+    pub uploaded: bool,
+    pub note: Option<String>,
+    pub pomodoro: bool,
 }
 
-#[derive(Insertable)]
-#[table_name = "tasks"]
 pub struct NewTask {
     pub name: String,
     pub started_at: i64,
+    pub finished_at: Option<i64>,
+    pub uploaded: bool,
+    pub note: Option<String>,
+}
+
+impl NewTask {
+    pub fn insert(&self, conn: &SqliteConnection) -> QueryResult<usize> {
+        diesel::insert_into(tasks::table)
+            .values((
+                name.eq(sealed(&self.name)),
+                started_at.eq(self.started_at),
+                finished_at.eq(self.finished_at),
+                uploaded.eq(self.uploaded),
+                tasks::note.eq(self.note.as_ref().map(sealed)),
+            ))
+            .execute(conn)
+    }
 }
 
 #[derive(QueryableByName)]
@@ -30,17 +49,124 @@ pub struct AggregatedTask {
     pub finished_at: Option<i64>,
     #[sql_type = "BigInt"]
     pub duration: i64,
+    #[sql_type = "BigInt"]
+    pub pomodoros: i64,
 }
 
 pub fn get_unfinished_task(task_name: &str, conn: &SqliteConnection) -> Vec<Task> {
     tasks::table
-        .filter(name.eq(task_name))
+        .filter(name.eq(sealed(task_name)))
         .filter(finished_at.is_null())
         .limit(1)
         .load::<Task>(conn)
         .unwrap()
 }
 
+// sessions still in progress count as running until now
+pub fn get_overlapping_tasks(
+    task_name: &str,
+    from_ts: i64,
+    to_ts: i64,
+    conn: &SqliteConnection,
+) -> QueryResult<Vec<Task>> {
+    tasks::table
+        .filter(name.eq(sealed(task_name)))
+        .filter(started_at.lt(to_ts))
+        .filter(finished_at.gt(from_ts).or(finished_at.is_null()))
+        .load::<Task>(conn)
+}
+
+pub fn get_task_sessions(
+    task_name: Option<&str>,
+    since: Option<i64>,
+    until: Option<i64>,
+    limit: Option<usize>,
+    oldest_first: bool,
+    conn: &SqliteConnection,
+) -> QueryResult<Vec<Task>> {
+    let mut query = if oldest_first {
+        tasks::table
+            .order((started_at.asc(), tasks::id.asc()))
+            .into_boxed()
+    } else {
+        tasks::table
+            .order((started_at.desc(), tasks::id.desc()))
+            .into_boxed()
+    };
+    if let Some(task_name) = task_name {
+        query = query.filter(name.eq(sealed(task_name)));
+    }
+    if let Some(since) = since {
+        query = query.filter(finished_at.ge(since).or(finished_at.is_null()));
+    }
+    if let Some(until) = until {
+        query = query.filter(started_at.le(until));
+    }
+    if let Some(limit) = limit {
+        query = query.limit(limit as i64);
+    }
+    query.load::<Task>(conn)
+}
+
+pub fn get_task_by_id(task_id: i32, conn: &SqliteConnection) -> QueryResult<Option<Task>> {
+    tasks::table.find(task_id).first::<Task>(conn).optional()
+}
+
+pub fn session_exists(
+    task_name: &str,
+    start_ts: i64,
+    conn: &SqliteConnection,
+) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        tasks::table
+            .filter(name.eq(sealed(task_name)))
+            .filter(started_at.eq(start_ts)),
+    ))
+    .get_result(conn)
+}
+
+pub fn get_task_names(in_progress_only: bool, conn: &SqliteConnection) -> QueryResult<Vec<String>> {
+    let mut query = tasks::table.select(name).distinct().into_boxed();
+    if in_progress_only {
+        query = query.filter(finished_at.is_null());
+    }
+    // sorted here, sqlite would sort the names of an encrypted database by
+    // their sealed text
+    let mut names = query.load::<String>(conn)?;
+    names.sort();
+    Ok(names)
+}
+
+// finishes every session in progress at the given time and returns them as
+// they were saved.
+pub fn finish_unfinished_tasks(timestamp: i64, conn: &SqliteConnection) -> QueryResult<Vec<Task>> {
+    let mut unfinished = tasks::table
+        .filter(finished_at.is_null())
+        .order(tasks::id.asc())
+        .load::<Task>(conn)?;
+    unfinished.sort_by(|a, b| a.name.cmp(&b.name));
+    for task in unfinished.iter_mut() {
+        task.finished_at = Some(std::cmp::max(timestamp, task.started_at));
+        diesel::update(tasks::table.find(task.id))
+            .set(finished_at.eq(task.finished_at))
+            .execute(conn)?;
+    }
+    Ok(unfinished)
+}
+
+pub fn get_tasks_to_upload(conn: &SqliteConnection) -> QueryResult<Vec<Task>> {
+    tasks::table
+        .filter(uploaded.eq(false))
+        .order(tasks::id.asc())
+        .load::<Task>(conn)
+}
+
+pub fn mark_tasks_uploaded(task_ids: &[i32], conn: &SqliteConnection) -> QueryResult<usize> {
+    diesel::update(tasks::table.filter(tasks::id.eq_any(task_ids)))
+        .set(uploaded.eq(true))
+        .execute(conn)
+}
+
 pub fn get_ts() -> Result<Duration, SystemTimeError> {
     SystemTime::now().duration_since(UNIX_EPOCH)
 }
@@ -8,191 +8,422 @@ pub mod database;
 pub mod managers;
 pub mod puncher;
 pub mod utils;
+pub mod views;
 
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, IsTerminal};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use ansi_term::Colour::{Cyan, Green, Purple, Red, Yellow};
 use chrono::{DateTime, Utc};
-use clap::{arg, Command};
+use chrono_tz::Tz;
+use clap::{arg, Arg, ArgMatches, Command};
+use clap_complete::Shell;
 use dateparser;
-use diesel_migrations::embed_migrations;
+use diesel::sqlite::SqliteConnection;
 
-use crate::database::{database::create_connection, task::*};
+use crate::api::client::configure_client;
+use crate::database::{
+    aggregate::{compact_sessions, count_aggregates},
+    database::{create_connection, default_database_path, DATABASE_FILE_NAME},
+    maintenance::{find_problems, finish_duplicate_open_sessions, swap_reversed_sessions, vacuum},
+    merge::{count_sessions, merge_database},
+    migrations::{applied_migrations, run_migrations},
+    sealed::{decrypt_database, encrypt_database, is_encrypted, unlock_database},
+    settings::{get_setting, set_setting, IGNORED_DATABASE},
+    task::*,
+};
 use crate::managers::{
     auth::AuthManager,
-    configs::fetch_configs,
-    keyring::{new_key_ring_manager, SecretsManager},
+    configs::{
+        fetch_configs, fetch_profile_configs, AppConfigs, ConfigFile, CONFIG_KEYS, DEFAULT_PROFILE,
+        DEFAULT_PROFILE_KEY,
+    },
+    keyring::{new_database_key_manager, new_key_ring_manager, SecretsManager},
 };
-use crate::puncher::Puncher;
+use crate::puncher::{Puncher, SessionFilter, SyncReport, TaskListItem, TaskPolicy, UploadReport};
 use crate::utils::{
-    clock::{seconds_to_duration, utc_ts_to_local_datetime},
-    printer::write_tab_written_message,
+    clock::{parse_age, parse_duration, seconds_to_duration, utc_ts_to_local_datetime},
+    color::{set_colors_enabled, Cyan, Green, Yellow},
+    completions::write_completions,
+    doctor::{run_checks, CheckStatus},
+    export::{ExportFormat, SessionExporter},
+    import::{parse_import, ImportSource},
+    printer::{
+        exit_with_error, output_format, set_output_format, write_progress_message, ErrorCode,
+        OutputFormat,
+    },
+    prompt::{confirm, edit_message},
+    report::ReportPeriod,
+    timer::{countdown, interrupt_flag},
+};
+use crate::views::{
+    account::{
+        show_account_list, show_account_use, show_logged_in, show_logged_out, show_sync,
+        show_upload, AccountDocument,
+    },
+    config::{show_checks, show_config_list, show_config_saved, show_config_value},
+    db::{
+        print_database_problems, show_compact, show_db_check, show_db_status, show_encryption,
+        show_merge, show_repair, show_vacuum, DatabaseStatus, DbRepairDocument,
+    },
+    tasks::{
+        show_cancelled, show_import, show_punch_in, show_punch_out, show_report, show_session,
+        show_sessions, show_status, show_switch, show_task_list, show_task_policy, show_task_stat,
+        show_timer, ReportRange, TimerDocument,
+    },
 };
-
-embed_migrations!("./migrations");
 
 fn main() -> Result<(), std::io::Error> {
-    let conn = create_connection().unwrap();
-    let cf = fetch_configs();
-    let sm = new_key_ring_manager();
-    let am = AuthManager::new(&cf, &sm);
-    let puncher = Puncher::new(&am, &cf, &conn);
-
-    let matches = Command::new("Punch CLI")
-        .subcommand_required(true)
-        .arg_required_else_help(true)
-        .subcommand(Command::new("login").about("log in to the server using GitHub account"))
-        .subcommand(Command::new("logout").about("log out from the server"))
-        .subcommand(
-            Command::new("status")
-                .about("start a new task")
-                .arg(arg!([NAME])),
-        )
-        .subcommand(
-            Command::new("in")
-                .about("start a new task")
-                .arg(arg!([NAME])),
-        )
-        .subcommand(
-            Command::new("out")
-                .about("finish a task that is in progress")
-                .arg(arg!([NAME])),
-        )
+    // the completion scripts call the hidden subcommand, so it is left out of
+    // the tree they are generated from
+    let matches = cli()
         .subcommand(
-            Command::new("cancel")
-                .about("cancel a task")
-                .arg(arg!([NAME])),
+            Command::new("__complete-tasks")
+                .hide(true)
+                .arg(arg!(--"in-progress" "only the tasks in progress")),
         )
-        .subcommand(
-            Command::new("get")
-                .about("prints how much time you spent for a given task")
-                .arg(arg!([NAME]))
-                .arg(arg!(--since[SINCE_TS]))
-                .arg(arg!(--until[UNTIL_TS])),
-        )
-        .subcommand(Command::new("list").about("list all tasks and their status"))
         .get_matches();
 
-    embedded_migrations::run(&conn).unwrap();
-    embedded_migrations::run_with_output(&conn, &mut std::io::stdout()).unwrap();
+    // a broken configuration only matters to the commands that need it
+    let configs = fetch_configs(matches.value_of("profile"));
+    let output = match matches
+        .value_of("output")
+        .or_else(|| configs.as_ref().ok().and_then(|cf| cf.output.as_deref()))
+    {
+        Some(format) => OutputFormat::parse(format)
+            .unwrap_or_else(|err| exit_with_error(ErrorCode::InvalidInput, err)),
+        None => OutputFormat::Text,
+    };
+    set_output_format(output);
+    set_colors_enabled(
+        output == OutputFormat::Text
+            && !matches.is_present("no-color")
+            && env::var_os("NO_COLOR").is_none(),
+    );
+
+    // completions are generated before anything else is set up, they work
+    // without a configuration or a database
+    if let Some(("completions", sub_matches)) = matches.subcommand() {
+        let shell = sub_matches
+            .value_of("SHELL")
+            .unwrap()
+            .parse::<Shell>()
+            .unwrap();
+        write_completions(shell, &mut cli(), &mut io::stdout());
+        return Ok(());
+    }
+
+    // the profile being edited may not exist yet
+    if let Some(("config", sub_matches)) = matches.subcommand() {
+        run_config_command(sub_matches, matches.value_of("profile"), &configs);
+        return Ok(());
+    }
+    if let Some(("account", sub_matches)) = matches.subcommand() {
+        run_account_command(sub_matches, matches.value_of("profile"));
+        return Ok(());
+    }
+
+    let db_override = matches.value_of_os("db").map(PathBuf::from).or_else(|| {
+        env::var_os("PUNCH_DB")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    });
+    // diagnoses what keeps the other commands from starting
+    if let Some(("doctor", _)) = matches.subcommand() {
+        run_doctor(
+            matches.value_of("profile"),
+            &configs,
+            db_override.as_deref(),
+        );
+    }
+
+    let cf = configs.unwrap_or_else(|err| exit_with_error(ErrorCode::InvalidInput, err));
+    // chrono reads TZ for the local time, so the profile's zone applies to
+    // every date shown or parsed from here on
+    if let Some(time_zone) = &cf.timezone {
+        env::set_var("TZ", time_zone);
+    }
+    if let Err(err) = configure_client(&cf) {
+        exit_with_error(ErrorCode::InvalidInput, err);
+    }
+    let db_path = match &db_override {
+        Some(path) => path.to_owned(),
+        None => default_database_path(&cf.profile)
+            .unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err)),
+    };
+    let conn = create_connection(&db_path)
+        .unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+    let sm = new_key_ring_manager(&cf.profile);
+    let am = AuthManager::new(&cf, &sm);
+    let puncher = Puncher::new(&am, &cf, &conn);
+
+    match run_migrations(&conn) {
+        Ok(applied) if !applied.is_empty() => {
+            write_progress_message(format!(
+                "{} applied {} migration(s) to {}, now at schema version {}",
+                Green.paint("MIGRATED:"),
+                applied.len(),
+                db_path.display(),
+                applied.last().unwrap()
+            ));
+        }
+        Ok(_) => {}
+        Err(err) => exit_with_error(
+            ErrorCode::CommandFailed,
+            format!("failed to migrate {}: {}", db_path.display(), err),
+        ),
+    }
+    let dkm = new_database_key_manager(&cf.profile);
+    // the status only counts rows, it tells an encrypted database apart
+    // even when its key is missing
+    let is_db_status = matches!(
+        matches.subcommand(),
+        Some(("db", db_matches)) if matches!(db_matches.subcommand(), Some(("status", _)))
+    );
+    if !is_db_status {
+        if let Err(err) = unlock_database(&conn, &dkm) {
+            exit_with_error(ErrorCode::CommandFailed, err);
+        }
+    }
+
+    // called by the completion scripts on every tab, so it stays offline
+    if let Some(("__complete-tasks", sub_matches)) = matches.subcommand() {
+        if let Ok(names) = puncher.completion_task_names(sub_matches.is_present("in-progress")) {
+            for name in names {
+                println!("{}", name);
+            }
+        }
+        return Ok(());
+    }
+
+    // maintenance stays local, nothing is merged or synced first
+    if let Some(("db", sub_matches)) = matches.subcommand() {
+        run_db_command(sub_matches, &conn, &db_path, &dkm);
+        return Ok(());
+    }
+
+    if db_override.is_none() && output == OutputFormat::Text {
+        offer_database_merge(&conn, &db_path);
+    }
+
+    let sync_report = sync_pending_punches(&puncher);
 
     match matches.subcommand() {
         Some(("in", sub_matches)) => {
             let task_name = sub_matches.value_of("NAME").unwrap();
-            match puncher.punch_in(task_name.to_owned()) {
-                Ok(timestamp) => {
-                    write_tab_written_message(format!(
-                        "{}\n{}\t{}",
-                        Cyan.paint("name\tstarted at"),
-                        task_name,
-                        Green.paint(utc_ts_to_local_datetime(timestamp)),
-                    ));
-                }
+            let tags = tag_values(sub_matches);
+            match puncher.punch_in(task_name.to_owned(), &tags) {
+                Ok(timestamp) => show_punch_in(task_name, timestamp, &tags, puncher.is_offline()),
                 Err(err) => {
-                    println!("{} {}", Red.paint("ERROR:"), Cyan.paint(err),);
-                    std::process::exit(1);
+                    exit_with_error(ErrorCode::CommandFailed, err);
                 }
             };
         }
         Some(("out", sub_matches)) => {
             let task_name = sub_matches.value_of("NAME").unwrap();
-            match puncher.punch_out(task_name.to_owned()) {
-                Ok(task) => {
-                    // TODO: this needs better error handling
-                    let ts = task.finished_at.unwrap();
-                    write_tab_written_message(format!(
-                        "{}\n{}\t{}\t{}",
-                        Cyan.paint("name\tfinished at\ttime spent"),
-                        task_name,
-                        Green.paint(utc_ts_to_local_datetime(ts)),
-                        Yellow.paint(seconds_to_duration(ts - task.started_at)),
-                    ));
+            let note = if sub_matches.is_present("edit") {
+                match edit_message(&format!(
+                    "\n# describe what was done on {}, lines starting with '#' are ignored\n",
+                    task_name
+                )) {
+                    Ok(message) => Some(message),
+                    Err(err) => {
+                        exit_with_error(ErrorCode::CommandFailed, err);
+                    }
+                }
+            } else {
+                sub_matches
+                    .value_of("message")
+                    .map(|message| message.trim().to_owned())
+            }
+            .filter(|message| !message.is_empty());
+            match puncher.punch_out(task_name.to_owned(), note, false) {
+                Ok(task) => show_punch_out(task_name, &task, puncher.is_offline()),
+                Err(err) => {
+                    exit_with_error(ErrorCode::CommandFailed, err);
+                }
+            };
+        }
+        Some(("switch", sub_matches)) => {
+            let task_name = sub_matches.value_of("NAME").unwrap();
+            let tags = tag_values(sub_matches);
+            match puncher.switch(task_name.to_owned(), &tags) {
+                Ok(report) => show_switch(task_name, &report, &tags, puncher.is_offline()),
+                Err(err) => {
+                    exit_with_error(ErrorCode::CommandFailed, err);
                 }
+            };
+        }
+        Some(("add", sub_matches)) => {
+            let task_name = sub_matches.value_of("NAME").unwrap();
+            let tags = tag_values(sub_matches);
+            let from_ts = parse_time_arg(sub_matches.value_of("from").unwrap(), "from");
+            let to_ts = parse_time_arg(sub_matches.value_of("to").unwrap(), "to");
+            let note = sub_matches
+                .value_of("message")
+                .map(|message| message.trim().to_owned())
+                .filter(|message| !message.is_empty());
+            match puncher.add(
+                task_name.to_owned(),
+                from_ts,
+                to_ts,
+                &tags,
+                note,
+                sub_matches.is_present("force"),
+            ) {
+                Ok(task) => show_session(&task, puncher.is_offline()),
                 Err(err) => {
-                    println!("{} {}", Red.paint("ERROR:"), Cyan.paint(err));
-                    std::process::exit(1);
+                    exit_with_error(ErrorCode::CommandFailed, err);
                 }
             };
         }
+        Some(("edit", sub_matches)) => {
+            let session_id = match sub_matches.value_of("SESSION_ID").unwrap().parse::<i64>() {
+                Ok(session_id) => session_id,
+                Err(_) => {
+                    exit_with_error(ErrorCode::InvalidInput, "the session id must be a number");
+                }
+            };
+            let from_ts = sub_matches
+                .value_of("start")
+                .map(|start| parse_time_arg(start, "start"));
+            let to_ts = sub_matches
+                .value_of("end")
+                .map(|end| parse_time_arg(end, "end"));
+            if from_ts.is_none() && to_ts.is_none() {
+                exit_with_error(
+                    ErrorCode::InvalidInput,
+                    "nothing to change, give --start and/or --end",
+                );
+            }
+            match puncher.edit(session_id, from_ts, to_ts, sub_matches.is_present("force")) {
+                Ok(task) => show_session(&task, puncher.is_offline()),
+                Err(err) => {
+                    exit_with_error(ErrorCode::CommandFailed, err);
+                }
+            };
+        }
+        Some(("timer", sub_matches)) => {
+            let task_name = sub_matches.value_of("NAME").unwrap();
+            let tags = tag_values(sub_matches);
+            let focus = parse_duration_arg(sub_matches.value_of("DURATION").unwrap());
+            let pause = sub_matches
+                .value_of("break")
+                .map(parse_duration_arg)
+                .unwrap_or(0);
+            let cycles = match sub_matches.value_of("cycles").unwrap().parse::<usize>() {
+                Ok(cycles) if cycles > 0 => cycles,
+                _ => exit_with_error(
+                    ErrorCode::InvalidInput,
+                    "the number of cycles must be a positive number",
+                ),
+            };
+            let keep_partial = match (
+                sub_matches.is_present("keep"),
+                sub_matches.is_present("discard"),
+            ) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ if output == OutputFormat::Json => exit_with_error(
+                    ErrorCode::InvalidInput,
+                    "pass --keep or --discard to timer with json output, there is no one to ask",
+                ),
+                _ => None,
+            };
+            run_timer(
+                &puncher,
+                task_name,
+                &tags,
+                focus,
+                pause,
+                cycles,
+                keep_partial,
+            );
+        }
         Some(("cancel", sub_matches)) => {
             let task_name = sub_matches.value_of("NAME").unwrap();
             match puncher.cancel(task_name.to_string()) {
-                Ok(_) => {
-                    println!("Cancelled {}", Cyan.paint(task_name));
-                }
+                Ok(_) => show_cancelled(task_name, puncher.is_offline()),
                 Err(err) => {
-                    println!("{} {}", Red.paint("ERROR:"), Cyan.paint(err));
-                    std::process::exit(1);
+                    exit_with_error(ErrorCode::CommandFailed, err);
                 }
             };
         }
         Some(("login", _)) => {
-            let token = am.get_access_token().unwrap_or_else(|| am.login());
+            if cf.api_endpoint.is_empty() {
+                exit_with_error(
+                    ErrorCode::InvalidInput,
+                    "no server is configured, set one with punch config set endpoint <URL>",
+                );
+            }
+            let token = match am.get_access_token() {
+                Some(token) => token,
+                None => am.login().unwrap_or_else(|err| {
+                    exit_with_error(
+                        ErrorCode::CommandFailed,
+                        format!("failed to log in: {}", err),
+                    )
+                }),
+            };
             match am.verify_login(&token) {
                 Ok(_) => {
                     sm.save_secrets(&token);
-                    println!("{}", Green.paint("successfully logged in"));
+                    // keeps a copy of the policy for punches made while offline
+                    let _ = puncher.task_policy();
+                    let local_count = puncher.count_tasks_to_upload().unwrap_or(0);
+                    show_logged_in(local_count);
+                    if local_count > 0
+                        && output == OutputFormat::Text
+                        && confirm(&format!(
+                            "found {} session(s) recorded locally, upload them to the server?",
+                            local_count
+                        ))
+                    {
+                        upload_local_history(&puncher, &token);
+                    }
                 }
                 Err(err) => {
                     sm.remove_secret();
-                    println!("{} {}", Red.paint("ERROR:"), Cyan.paint(err.message));
-                    std::process::exit(1);
+                    exit_with_error(ErrorCode::CommandFailed, err.message);
                 }
             };
         }
+        Some(("migrate-to-server", _)) => match am.get_access_token() {
+            Some(token) => match puncher.count_tasks_to_upload() {
+                Ok(0) => show_upload(&UploadReport {
+                    uploaded: 0,
+                    skipped: 0,
+                    duration: 0,
+                }),
+                Ok(_) => upload_local_history(&puncher, &token),
+                Err(err) => {
+                    exit_with_error(ErrorCode::CommandFailed, err);
+                }
+            },
+            None => {
+                exit_with_error(
+                    ErrorCode::NotLoggedIn,
+                    "you must be logged in to upload local sessions",
+                );
+            }
+        },
         Some(("logout", _)) => {
             sm.remove_secret();
-            println!("{}", Green.paint("successfully logged out"));
+            show_logged_out();
         }
-        Some(("list", _)) => match puncher.list() {
+        Some(("list", sub_matches)) => match puncher.list(&tag_filter_values(sub_matches)) {
             Ok(tasks) => {
-                write_tab_written_message(
-                    tasks
-                        .iter()
-                        .map(|task| {
-                            let duration = Purple.paint(seconds_to_duration(task.duration));
-                            return match task.finished_at {
-                                Some(_) => {
-                                    format!(
-                                        "{}\t({})\t{}\t{}",
-                                        task.name,
-                                        Green.paint("complete"),
-                                        String::new(),
-                                        duration,
-                                    )
-                                }
-                                None => {
-                                    let now_dt = get_ts().unwrap();
-                                    let now_ts = now_dt.as_secs() as i64;
-                                    format!(
-                                        "{}\t({})\t{}\t{}",
-                                        task.name,
-                                        Red.paint("in progress"),
-                                        Yellow.paint(seconds_to_duration(now_ts - task.started_at)),
-                                        duration,
-                                    )
-                                }
-                            };
-                        })
-                        .fold(
-                            Cyan.paint(
-                                "name\tstatus\tcurrent total\ttotal (minus current total)\n",
-                            )
-                            .to_string(),
-                            |a, b| a + &b + "\n",
-                        ),
-                );
+                show_task_list(&tasks, sub_matches.is_present("tree"), puncher.is_offline())
             }
             Err(err) => {
-                println!("{} {}", Red.paint("ERROR:"), Cyan.paint(err));
-                std::process::exit(1);
+                exit_with_error(ErrorCode::CommandFailed, err);
             }
         },
         Some(("get", sub_matches)) => {
-            if !sub_matches.is_present("NAME") {
-                panic!("you must provide the task name")
-            }
-            let task_name = sub_matches.value_of("NAME").unwrap();
+            let task_name = sub_matches.value_of("NAME").map(|name| name.to_owned());
             let epoch_dt: DateTime<Utc> = UNIX_EPOCH.into();
             let now_dt: DateTime<Utc> = SystemTime::now().into();
             let since_dt: DateTime<Utc> = if sub_matches.is_present("since") {
@@ -201,42 +432,905 @@ fn main() -> Result<(), std::io::Error> {
                     .unwrap_or("1970-01-01 00:00 UTC");
                 match dateparser::parse(since_arg) {
                     Ok(dt) => dt,
-                    Err(_) => panic!("failed to parse 'since' time value"),
+                    Err(_) => exit_with_error(
+                        ErrorCode::InvalidInput,
+                        "failed to parse 'since' time value",
+                    ),
                 }
             } else {
                 epoch_dt
             };
             let until_dt: DateTime<Utc> = if sub_matches.is_present("until") {
                 let until_default = now_dt.to_string();
-                let until_arg = sub_matches.value_of("since").unwrap_or(&until_default);
+                let until_arg = sub_matches.value_of("until").unwrap_or(&until_default);
                 match dateparser::parse(until_arg) {
                     Ok(dt) => dt,
-                    Err(_) => panic!("failed to parse 'until' time value"),
+                    Err(_) => exit_with_error(
+                        ErrorCode::InvalidInput,
+                        "failed to parse 'until' time value",
+                    ),
                 }
             } else {
                 now_dt
             };
             let since = since_dt.signed_duration_since(epoch_dt).num_seconds();
             let until = until_dt.signed_duration_since(epoch_dt).num_seconds();
-            match puncher.get(task_name.to_owned(), since, until) {
-                Ok(stat) => write_tab_written_message(format!(
-                    "{}\n{}\t({})\t{}",
-                    Cyan.paint("name\tstatus\ttime spent"),
-                    stat.name,
-                    if stat.status == "in progress" {
-                        Red.paint("in progress")
-                    } else {
-                        Green.paint("complete")
-                    },
-                    Yellow.paint(seconds_to_duration(stat.duration)),
-                )),
+            match puncher.get(task_name, since, until, &tag_filter_values(sub_matches)) {
+                Ok(stat) => show_task_stat(&stat, since, until, puncher.is_offline()),
+                Err(err) => {
+                    exit_with_error(ErrorCode::CommandFailed, err);
+                }
+            }
+        }
+        Some(("policy", sub_matches)) => {
+            let policy_op = match sub_matches.value_of("POLICY") {
+                Some(policy) => {
+                    let policy = TaskPolicy::parse(policy)
+                        .unwrap_or_else(|err| exit_with_error(ErrorCode::InvalidInput, err));
+                    puncher.set_task_policy(policy).map(|_| policy)
+                }
+                None => puncher.task_policy(),
+            };
+            match policy_op {
+                Ok(policy) => show_task_policy(policy, puncher.is_offline()),
+                Err(err) => {
+                    exit_with_error(ErrorCode::CommandFailed, err);
+                }
+            }
+        }
+        Some(("status", sub_matches)) => match puncher.list(&[]) {
+            Ok(tasks) => {
+                let task_name = sub_matches.value_of("NAME");
+                let in_progress: Vec<TaskListItem> = tasks
+                    .into_iter()
+                    .filter(|task| task.finished_at.is_none())
+                    .filter(|task| match task_name {
+                        Some(task_name) => task.name.eq_ignore_ascii_case(task_name),
+                        None => true,
+                    })
+                    .collect();
+                show_status(&in_progress, task_name, puncher.is_offline());
+            }
+            Err(err) => {
+                exit_with_error(ErrorCode::CommandFailed, err);
+            }
+        },
+        Some(("log", sub_matches)) => {
+            let task_name = sub_matches.value_of("NAME").map(|name| name.to_owned());
+            let since = sub_matches
+                .value_of("since")
+                .map(|since| parse_time_arg(since, "since"));
+            let until = sub_matches
+                .value_of("until")
+                .map(|until| parse_time_arg(until, "until"));
+            let limit = match sub_matches
+                .value_of("limit")
+                .map(|limit| limit.parse::<NonZeroUsize>())
+            {
+                Some(Ok(limit)) => Some(limit.get()),
+                Some(Err(_)) => {
+                    exit_with_error(
+                        ErrorCode::InvalidInput,
+                        "the limit must be a positive number",
+                    );
+                }
+                None => None,
+            };
+            let filter = SessionFilter {
+                task_name,
+                since,
+                until,
+                limit,
+                oldest_first: false,
+            };
+            match puncher.sessions(&filter) {
+                Ok(sessions) => show_sessions(&sessions, puncher.is_offline()),
                 Err(err) => {
-                    println!("{} {}", Red.paint("ERROR:"), Cyan.paint(err));
-                    std::process::exit(1);
+                    exit_with_error(ErrorCode::CommandFailed, err);
                 }
             }
         }
+        Some(("report", sub_matches)) => {
+            let period = ReportPeriod::parse(sub_matches.value_of("period").unwrap()).unwrap();
+            let by_day = sub_matches.value_of("group-by") == Some("day");
+            let now_ts = get_ts().unwrap().as_secs() as i64;
+            let since = match sub_matches.value_of("since") {
+                Some(since) => parse_time_arg(since, "since"),
+                None => period.start_of(now_ts),
+            };
+            let until = match sub_matches.value_of("until") {
+                Some(until) => parse_time_arg(until, "until"),
+                None => now_ts,
+            };
+            let bucket = if by_day { ReportPeriod::Day } else { period };
+            match puncher.report(bucket, since, until) {
+                Ok(rows) => {
+                    let range = ReportRange {
+                        period,
+                        by_day,
+                        since,
+                        until,
+                    };
+                    show_report(&rows, &range, puncher.is_offline());
+                }
+                Err(err) => {
+                    exit_with_error(ErrorCode::CommandFailed, err);
+                }
+            }
+        }
+        Some(("export", sub_matches)) => {
+            let format = ExportFormat::parse(sub_matches.value_of("format").unwrap()).unwrap();
+            let time_zone = match sub_matches.value_of("tz").map(|tz| tz.parse::<Tz>()) {
+                Some(Ok(tz)) => Some(tz),
+                Some(Err(err)) => {
+                    exit_with_error(ErrorCode::InvalidInput, err);
+                }
+                None => None,
+            };
+            let filter = SessionFilter {
+                task_name: sub_matches.value_of("task").map(|task| task.to_owned()),
+                since: sub_matches
+                    .value_of("since")
+                    .map(|since| parse_time_arg(since, "since")),
+                until: sub_matches
+                    .value_of("until")
+                    .map(|until| parse_time_arg(until, "until")),
+                limit: None,
+                oldest_first: true,
+            };
+            let now_ts = get_ts().unwrap().as_secs() as i64;
+            let mut exporter =
+                SessionExporter::new(format, time_zone, now_ts, BufWriter::new(io::stdout()));
+            let export_op = exporter
+                .begin()
+                .and_then(|_| {
+                    puncher.stream_sessions(&filter, |page| exporter.write_sessions(&page))
+                })
+                .and_then(|_| exporter.finish());
+            if let Err(err) = export_op {
+                exit_with_error(ErrorCode::CommandFailed, err);
+            }
+            // stdout carries the exported data, so the notice goes elsewhere
+            if puncher.is_offline() {
+                eprintln!(
+                    "{} the server is unreachable, only sessions recorded on this machine were exported",
+                    Yellow.paint("OFFLINE:")
+                );
+            }
+        }
+        Some(("import", sub_matches)) => {
+            let source = ImportSource::parse(sub_matches.value_of("from").unwrap()).unwrap();
+            let parsed = fs::read_to_string(sub_matches.value_of("FILE").unwrap())
+                .map_err(|err| err.to_string())
+                .and_then(|content| parse_import(source, &content, sub_matches.value_of("task")));
+            let import_op = parsed.and_then(|parsed| {
+                puncher
+                    .import(&parsed.sessions)
+                    .map(|report| (report, parsed.ignored))
+            });
+            match import_op {
+                Ok((report, ignored)) => show_import(&report, ignored, puncher.is_offline()),
+                Err(err) => {
+                    exit_with_error(ErrorCode::CommandFailed, err);
+                }
+            }
+        }
+        // a refused token is reported before it turns into not being logged in
+        Some(("sync", _)) => match sync_report.map(|report| (report, am.get_access_token())) {
+            Ok((report, Some(_))) => show_sync(&report),
+            Ok((_, None)) => {
+                exit_with_error(ErrorCode::NotLoggedIn, "you must be logged in to sync");
+            }
+            Err(err) => {
+                exit_with_error(ErrorCode::CommandFailed, err);
+            }
+        },
         _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
     }
     Ok(())
 }
+
+fn cli() -> Command<'static> {
+    Command::new("Punch CLI")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            arg!(--output <FORMAT> "print text for people or json for scripts")
+                .required(false)
+                .global(true)
+                .possible_values(["text", "json"]),
+        )
+        .arg(arg!(--"no-color" "print without colors").global(true))
+        .arg(
+            arg!(--profile <PROFILE> "the profile of the configuration file to use")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            arg!(--db <PATH> "the database to use instead of the one in the data directory")
+                .required(false)
+                .global(true)
+                .allow_invalid_utf8(true),
+        )
+        .subcommand(Command::new("login").about("log in to the server using GitHub account"))
+        .subcommand(Command::new("logout").about("log out from the server"))
+        .subcommand(
+            Command::new("migrate-to-server")
+                .about("upload tasks recorded in the local database to the server"),
+        )
+        .subcommand(
+            Command::new("policy")
+                .about("show or change what happens when punching in while another task runs")
+                .arg(
+                    arg!([POLICY] "concurrent allows any number of running tasks, single refuses to start another one and auto-switch finishes the running ones first")
+                        .possible_values(["concurrent", "single", "auto-switch"]),
+                ),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("show the tasks in progress")
+                .arg(arg!([NAME])),
+        )
+        .subcommand(
+            Command::new("in")
+                .about("start a new task")
+                .arg(arg!([NAME]))
+                .arg(arg!([TAGS] ... "tags for the session, e.g. +acme +billable")),
+        )
+        .subcommand(
+            Command::new("out")
+                .about("finish a task that is in progress")
+                .arg(arg!([NAME]))
+                .arg(
+                    arg!(-m --message <MESSAGE> "what was done during the session").required(false),
+                )
+                .arg(arg!(-e --edit "write the message in $EDITOR").conflicts_with("message")),
+        )
+        .subcommand(
+            Command::new("switch")
+                .about("finish the tasks in progress and start another one in their place")
+                .arg(arg!(<NAME>))
+                .arg(arg!([TAGS] ... "tags for the session, e.g. +acme +billable")),
+        )
+        .subcommand(
+            Command::new("add")
+                .about("record a session after the fact")
+                .arg(arg!(<NAME>))
+                .arg(arg!([TAGS] ... "tags for the session, e.g. +acme +billable"))
+                .arg(arg!(--from <FROM> "when the session started, e.g. \"09:00\""))
+                .arg(arg!(--to <TO> "when the session finished, e.g. \"10:30\""))
+                .arg(
+                    arg!(-m --message <MESSAGE> "what was done during the session").required(false),
+                )
+                .arg(arg!(--force "record the session even if it overlaps another one")),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("change when a session started or finished")
+                .arg(arg!(<SESSION_ID> "the id shown by the log command"))
+                .arg(arg!(--start <START> "when the session started").required(false))
+                .arg(arg!(--end <END> "when the session finished").required(false))
+                .arg(arg!(--force "save the session even if it overlaps another one")),
+        )
+        .subcommand(
+            Command::new("timer")
+                .about("work on a task for a set time and punch out when it is up")
+                .arg(arg!(<NAME>))
+                .arg(arg!(<DURATION> "how long each interval lasts, e.g. 25m or 1h30m"))
+                .arg(arg!([TAGS] ... "tags for the sessions, e.g. +acme +billable"))
+                .arg(arg!(--break <BREAK> "pause between the intervals, e.g. 5m").required(false))
+                .arg(
+                    arg!(--cycles <CYCLES> "how many intervals to run")
+                        .required(false)
+                        .default_value("1"),
+                )
+                .arg(arg!(--keep "keep the partial session when the timer is stopped early"))
+                .arg(
+                    arg!(--discard "cancel the partial session when the timer is stopped early")
+                        .conflicts_with("keep"),
+                ),
+        )
+        .subcommand(
+            Command::new("cancel")
+                .about("cancel a task")
+                .arg(arg!([NAME])),
+        )
+        .subcommand(
+            Command::new("get")
+                .about("prints how much time you spent for a given task")
+                .arg(arg!([NAME]))
+                .arg(arg!(--since[SINCE_TS]))
+                .arg(arg!(--until[UNTIL_TS]))
+                .arg(tag_filter_arg()),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("list all tasks and their status")
+                .arg(tag_filter_arg())
+                .arg(arg!(--tree "nest tasks by their path, e.g. acme/backend, with subtotals")),
+        )
+        .subcommand(
+            Command::new("log")
+                .about("list individual sessions, newest first, with their ids and notes")
+                .arg(arg!([NAME]))
+                .arg(arg!(--since <SINCE> "only sessions running after this time").required(false))
+                .arg(arg!(--until <UNTIL> "only sessions running before this time").required(false))
+                .arg(arg!(--limit <LIMIT> "show at most this many sessions").required(false)),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("prints a timesheet of the time spent per day, week or month")
+                .arg(
+                    arg!(--period <PERIOD> "the length of each period")
+                        .required(false)
+                        .possible_values(["day", "week", "month"])
+                        .default_value("week"),
+                )
+                .arg(
+                    arg!(--"group-by" <GROUP_BY> "what to total within each period")
+                        .required(false)
+                        .possible_values(["task", "day"])
+                        .default_value("task"),
+                )
+                .arg(
+                    arg!(--since <SINCE> "defaults to the start of the current period")
+                        .required(false),
+                )
+                .arg(arg!(--until <UNTIL> "defaults to now").required(false)),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("write individual sessions out for spreadsheets and other tools")
+                .arg(
+                    arg!(--format <FORMAT>)
+                        .required(false)
+                        .possible_values(["csv", "json", "ndjson"])
+                        .default_value("csv"),
+                )
+                .arg(arg!(--since <SINCE> "only sessions running after this time").required(false))
+                .arg(arg!(--until <UNTIL> "only sessions running before this time").required(false))
+                .arg(arg!(--task <TASK> "only sessions of the given task").required(false))
+                .arg(
+                    arg!(--tz <TIME_ZONE> "time zone of the timestamps, e.g. Europe/Berlin, defaults to the local one")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("bring in sessions tracked with other tools")
+                .arg(
+                    arg!(--from <SOURCE> "the tool the file was exported from")
+                        .possible_values(["toggl-csv", "timewarrior", "ics"]),
+                )
+                .arg(arg!(<FILE> "the exported file"))
+                .arg(
+                    arg!(--task <TASK> "record every session under this task instead")
+                        .required(false),
+                ),
+        )
+        .subcommand(Command::new("sync").about("send punches recorded while offline to the server"))
+        .subcommand(
+            Command::new("config")
+                .about("show or change the settings of the configuration file")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("get")
+                        .about("print the value a setting has in the current profile")
+                        .arg(arg!(<KEY>).possible_values(CONFIG_KEYS)),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("change a setting of the current profile, an empty value removes it")
+                        .arg(arg!(<KEY>).possible_values(CONFIG_KEYS))
+                        .arg(arg!(<VALUE>)),
+                )
+                .subcommand(Command::new("list").about("list the profiles and their settings")),
+        )
+        .subcommand(
+            Command::new("account")
+                .about("switch between the servers punch is logged in to")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("use")
+                        .about("make a profile of the configuration file the current account")
+                        .arg(arg!(<NAME>)),
+                )
+                .subcommand(
+                    Command::new("list").about("list the accounts and whether they are logged in"),
+                ),
+        )
+        .subcommand(
+            Command::new("db")
+                .about("inspect and maintain the local database")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("status").about("show where the database is and the migrations applied to it"),
+                )
+                .subcommand(
+                    Command::new("check").about("look for damage and for sessions that cannot be right"),
+                )
+                .subcommand(Command::new("vacuum").about("rebuild the database file to reclaim free space"))
+                .subcommand(
+                    Command::new("compact")
+                        .about("roll the sessions finished before a given age into daily totals per task")
+                        .arg(arg!(--"older-than" <AGE> "e.g. 90d, 6mo or 1y"))
+                        .arg(arg!(-y --yes "compact without asking").required(false)),
+                )
+                .subcommand(
+                    Command::new("repair")
+                        .about("fix the sessions found by punch db check")
+                        .arg(arg!(-y --yes "apply every fix without asking").required(false)),
+                )
+                .subcommand(
+                    Command::new("encrypt")
+                        .about("encrypt the task names, notes and tags with a key kept in the keyring")
+                        .long_about("encrypt the task names, notes and tags with a key kept in the keyring. \
+                            equal names, notes and tags are encrypted alike, so the file still shows which \
+                            sessions share them and how often, and the times are left as they are."),
+                )
+                .subcommand(Command::new("decrypt").about("store the database in plain text again")),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("check the configuration, keyring, database and server for problems"),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("print a completion script, e.g. punch completions bash > /etc/bash_completion.d/punch")
+                .arg(arg!(<SHELL>).possible_values(["bash", "zsh", "fish"])),
+        )
+}
+
+fn tag_values(sub_matches: &ArgMatches) -> Vec<String> {
+    let tags: Vec<String> = sub_matches
+        .values_of("TAGS")
+        .unwrap_or_default()
+        .map(|tag| tag.to_owned())
+        .collect();
+    if let Some(invalid) = tags.iter().find(|tag| !tag.starts_with('+')) {
+        exit_with_error(
+            ErrorCode::InvalidInput,
+            format!("tags must start with '+', got '{}'", invalid),
+        );
+    }
+    tags
+}
+
+// times without a date or timezone, such as "09:00", are read as today in the
+// local timezone.
+fn parse_time_arg(value: &str, arg_name: &str) -> i64 {
+    match dateparser::parse(value) {
+        Ok(dt) => dt.timestamp(),
+        Err(_) => {
+            exit_with_error(
+                ErrorCode::InvalidInput,
+                format!("failed to parse '{}' time value", arg_name),
+            );
+        }
+    }
+}
+
+fn parse_duration_arg(value: &str) -> i64 {
+    parse_duration(value).unwrap_or_else(|err| exit_with_error(ErrorCode::InvalidInput, err))
+}
+
+fn tag_filter_arg<'a>() -> Arg<'a> {
+    arg!(--tag <TAG> "only count sessions with the given tag, can be repeated")
+        .required(false)
+        .multiple_occurrences(true)
+}
+
+fn tag_filter_values(sub_matches: &ArgMatches) -> Vec<String> {
+    sub_matches
+        .values_of("tag")
+        .unwrap_or_default()
+        .map(|tag| tag.to_owned())
+        .collect()
+}
+
+// every interval is a session of its own, only the ones that ran until the
+// end count as pomodoros.
+fn run_timer<T: SecretsManager>(
+    puncher: &Puncher<T>,
+    task_name: &str,
+    tags: &[String],
+    focus: i64,
+    pause: i64,
+    cycles: usize,
+    keep_partial: Option<bool>,
+) {
+    let interrupted =
+        interrupt_flag().unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+    let mut document = TimerDocument {
+        name: task_name,
+        cycles,
+        completed: 0,
+        stopped_early: false,
+        partial_session: None,
+        offline: false,
+    };
+    for cycle in 1..=cycles {
+        if let Err(err) = puncher.punch_in(task_name.to_owned(), tags) {
+            exit_with_error(ErrorCode::CommandFailed, err);
+        }
+        let label = format!("{} {}/{}", task_name, cycle, cycles);
+        if !countdown(&label, focus, &interrupted) {
+            let keep = keep_partial.unwrap_or_else(|| {
+                confirm(&format!(
+                    "the timer was stopped early, keep the partial {} session?",
+                    task_name
+                ))
+            });
+            let stop_op = if keep {
+                puncher
+                    .punch_out(task_name.to_owned(), None, false)
+                    .map(|_| ())
+            } else {
+                puncher.cancel(task_name.to_owned())
+            };
+            if let Err(err) = stop_op {
+                exit_with_error(ErrorCode::CommandFailed, err);
+            }
+            let partial_session = if keep { "kept" } else { "cancelled" };
+            write_progress_message(format!(
+                "{} the partial session was {}",
+                Yellow.paint("STOPPED:"),
+                partial_session
+            ));
+            document.stopped_early = true;
+            document.partial_session = Some(partial_session);
+            break;
+        }
+        if let Err(err) = puncher.punch_out(task_name.to_owned(), None, true) {
+            exit_with_error(ErrorCode::CommandFailed, err);
+        }
+        document.completed += 1;
+        write_progress_message(format!(
+            "{} {} {}/{} ({})",
+            Green.paint("finished"),
+            Cyan.paint(task_name),
+            cycle,
+            cycles,
+            Yellow.paint(seconds_to_duration(focus))
+        ));
+        if cycle < cycles && pause > 0 && !countdown("break", pause, &interrupted) {
+            write_progress_message(format!(
+                "{} the timer was stopped during a break",
+                Yellow.paint("STOPPED:")
+            ));
+            document.stopped_early = true;
+            break;
+        }
+    }
+    document.offline = puncher.is_offline();
+    show_timer(&document);
+}
+
+fn sync_pending_punches<T: SecretsManager>(puncher: &Puncher<T>) -> Result<SyncReport, String> {
+    let sync_op = puncher.sync();
+    if let Ok(report) = &sync_op {
+        for rejected in report.rejected.iter() {
+            write_progress_message(format!(
+                "{} the server rejected {}",
+                Yellow.paint("WARNING:"),
+                rejected
+            ));
+        }
+    }
+    sync_op
+}
+
+fn upload_local_history<T: SecretsManager>(puncher: &Puncher<T>, token: &str) {
+    match puncher.upload_local_history(token) {
+        Ok(report) => show_upload(&report),
+        Err(err) => {
+            exit_with_error(ErrorCode::CommandFailed, err);
+        }
+    }
+}
+
+fn run_config_command(
+    sub_matches: &ArgMatches,
+    profile: Option<&str>,
+    configs: &Result<AppConfigs, String>,
+) {
+    let mut file =
+        ConfigFile::load().unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+    let profile = file.active_profile(profile);
+    let default_profile = file
+        .default_profile
+        .to_owned()
+        .unwrap_or_else(|| String::from(DEFAULT_PROFILE));
+    match sub_matches.subcommand() {
+        Some(("get", get_matches)) => {
+            let key = get_matches.value_of("KEY").unwrap();
+            let value = if key == DEFAULT_PROFILE_KEY {
+                Some(default_profile)
+            } else {
+                configs
+                    .as_ref()
+                    .unwrap_or_else(|err| exit_with_error(ErrorCode::InvalidInput, err))
+                    .get(key)
+                    .unwrap_or_else(|err| exit_with_error(ErrorCode::InvalidInput, err))
+            };
+            show_config_value(&profile, key, value.as_deref());
+        }
+        Some(("set", set_matches)) => {
+            let key = set_matches.value_of("KEY").unwrap();
+            let value = set_matches.value_of("VALUE").unwrap();
+            let saved = if key == DEFAULT_PROFILE_KEY {
+                file.default_profile = Some(value.trim().to_owned()).filter(|v| !v.is_empty());
+                file.default_profile.to_owned()
+            } else {
+                let settings = file.profiles.entry(profile.clone()).or_default();
+                if let Err(err) = settings.set(key, value) {
+                    exit_with_error(ErrorCode::InvalidInput, err);
+                }
+                settings.get(key).unwrap()
+            };
+            if let Err(err) = file.save() {
+                exit_with_error(ErrorCode::CommandFailed, err);
+            }
+            show_config_saved(&profile, key, saved.as_deref(), key != DEFAULT_PROFILE_KEY);
+        }
+        Some(("list", _)) => {
+            let path = ConfigFile::path()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default();
+            show_config_list(&path, &default_profile, &profile, &file.profiles);
+        }
+        _ => {}
+    }
+}
+
+// an account is a profile of the configuration file, with its own token in
+// the keyring and its own database
+fn run_account_command(sub_matches: &ArgMatches, profile: Option<&str>) {
+    let mut file =
+        ConfigFile::load().unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+    match sub_matches.subcommand() {
+        Some(("use", use_matches)) => {
+            let name = use_matches.value_of("NAME").unwrap().trim();
+            if name != DEFAULT_PROFILE && !file.profiles.contains_key(name) {
+                exit_with_error(
+                    ErrorCode::InvalidInput,
+                    format!(
+                        "unknown account '{}', create it with punch --profile {} config set endpoint <URL>",
+                        name, name
+                    ),
+                );
+            }
+            file.default_profile = Some(name.to_owned());
+            if let Err(err) = file.save() {
+                exit_with_error(ErrorCode::CommandFailed, err);
+            }
+            show_account_use(name);
+        }
+        Some(("list", _)) => {
+            let active = file.active_profile(profile);
+            let mut names: Vec<&str> = file.profiles.keys().map(|name| name.as_str()).collect();
+            if !file.profiles.contains_key(DEFAULT_PROFILE) {
+                names.insert(0, DEFAULT_PROFILE);
+            }
+            let accounts: Vec<AccountDocument> = names
+                .into_iter()
+                .map(|name| {
+                    let configs = match fetch_profile_configs(name) {
+                        Ok(configs) => configs,
+                        Err(err) => exit_with_error(ErrorCode::InvalidInput, err),
+                    };
+                    let sm = new_key_ring_manager(name);
+                    let token = AuthManager::new(&configs, &sm).token_state();
+                    AccountDocument {
+                        name,
+                        endpoint: configs.api_endpoint,
+                        token: token.as_str(),
+                    }
+                })
+                .collect();
+            show_account_list(&active, accounts);
+        }
+        _ => {}
+    }
+}
+
+// exits with 1 when a check failed, so scripts can tell a broken setup
+fn run_doctor(
+    profile: Option<&str>,
+    configs: &Result<AppConfigs, String>,
+    db_path: Option<&Path>,
+) -> ! {
+    let checks = run_checks(profile, configs, db_path);
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count();
+    show_checks(&checks, failed);
+    std::process::exit(if failed == 0 { 0 } else { 1 });
+}
+
+fn run_db_command<T: SecretsManager>(
+    sub_matches: &ArgMatches,
+    conn: &SqliteConnection,
+    db_path: &Path,
+    dkm: &T,
+) {
+    let file_size = || fs::metadata(db_path).map(|meta| meta.len()).unwrap_or(0);
+    match sub_matches.subcommand() {
+        Some(("status", _)) => {
+            let migrations = applied_migrations(conn)
+                .unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+            let sessions = count_sessions(conn)
+                .unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+            let compacted_days = count_aggregates(conn)
+                .unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+            show_db_status(&DatabaseStatus {
+                path: db_path,
+                size: file_size(),
+                sessions,
+                compacted_days,
+                encrypted: is_encrypted(conn),
+                migrations: &migrations,
+            });
+        }
+        Some(("check", _)) => {
+            let problems = find_problems(conn)
+                .unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+            show_db_check(&problems);
+            if !problems.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Some(("vacuum", _)) => {
+            let size_before = file_size();
+            if let Err(err) = vacuum(conn) {
+                exit_with_error(ErrorCode::CommandFailed, err);
+            }
+            show_vacuum(db_path, size_before, file_size());
+        }
+        Some(("compact", compact_matches)) => {
+            let age = parse_age(compact_matches.value_of("older-than").unwrap())
+                .unwrap_or_else(|err| exit_with_error(ErrorCode::InvalidInput, err));
+            if output_format() == OutputFormat::Json && !compact_matches.is_present("yes") {
+                exit_with_error(
+                    ErrorCode::InvalidInput,
+                    "pass --yes to compact with json output, there is no one to ask",
+                );
+            }
+            let now_ts = Utc::now().timestamp();
+            // whole days only, the sessions of a day are compacted together
+            let before = ReportPeriod::Day.start_of(now_ts - age);
+            if !compact_matches.is_present("yes")
+                && !confirm(&format!(
+                    "roll the sessions finished before {} into daily totals? they cannot be edited or exported one by one afterwards",
+                    utc_ts_to_local_datetime(before)
+                ))
+            {
+                return;
+            }
+            let report = compact_sessions(before, conn)
+                .unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+            show_compact(before, &report);
+        }
+        Some(("encrypt", _)) => {
+            if let Err(err) = encrypt_database(conn, dkm) {
+                exit_with_error(ErrorCode::CommandFailed, err);
+            }
+            show_encryption(db_path, true);
+        }
+        Some(("decrypt", _)) => {
+            if let Err(err) = decrypt_database(conn) {
+                exit_with_error(ErrorCode::CommandFailed, err);
+            }
+            show_encryption(db_path, false);
+        }
+        Some(("repair", repair_matches)) => {
+            let assume_yes = repair_matches.is_present("yes");
+            let is_json = output_format() == OutputFormat::Json;
+            if is_json && !assume_yes {
+                exit_with_error(
+                    ErrorCode::InvalidInput,
+                    "pass --yes to repair with json output, there is no one to ask",
+                );
+            }
+            let problems = find_problems(conn)
+                .unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+            if !is_json {
+                print_database_problems(&problems);
+            }
+            let mut report = DbRepairDocument {
+                swapped_sessions: 0,
+                finished_sessions: 0,
+                unrepaired: problems.integrity.len(),
+            };
+            let agree = |question: String| assume_yes || confirm(&question);
+            let fail = |err: diesel::result::Error| -> ! {
+                exit_with_error(ErrorCode::CommandFailed, err)
+            };
+            if !problems.reversed_sessions.is_empty()
+                && agree(format!(
+                    "swap the start and finish of {} session(s)?",
+                    problems.reversed_sessions.len()
+                ))
+            {
+                report.swapped_sessions =
+                    swap_reversed_sessions(conn).unwrap_or_else(|err| fail(err));
+            }
+            if !problems.duplicate_open_sessions.is_empty()
+                && agree(String::from(
+                    "finish the older open sessions when the next one started?",
+                ))
+            {
+                report.finished_sessions =
+                    finish_duplicate_open_sessions(conn).unwrap_or_else(|err| fail(err));
+            }
+            show_repair(&report, !problems.is_empty());
+            // the damage to the file itself needs a backup
+            if report.unrepaired > 0 {
+                exit_with_error(
+                    ErrorCode::CommandFailed,
+                    format!(
+                        "sqlite found damage in {} that punch cannot repair, restore a backup of it",
+                        db_path.display()
+                    ),
+                );
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+// punch used to keep its database in whichever directory it was run from,
+// those histories are offered to be merged into the one in the data directory.
+fn offer_database_merge(conn: &SqliteConnection, db_path: &Path) {
+    if !io::stdin().is_terminal() {
+        return;
+    }
+    let stray_path = match Path::new(DATABASE_FILE_NAME).canonicalize() {
+        Ok(path) if path.is_file() => path,
+        _ => return,
+    };
+    if db_path.canonicalize().ok().as_ref() == Some(&stray_path) {
+        return;
+    }
+    let ignore_key = format!("{}:{}", IGNORED_DATABASE, stray_path.display());
+    if get_setting(&ignore_key, conn).is_some() {
+        return;
+    }
+    let source = match create_connection(&stray_path) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+    let session_count = match count_sessions(&source) {
+        Ok(count) if count > 0 => count,
+        _ => return,
+    };
+    if !confirm(&format!(
+        "found {} session(s) in {}, merge them into {}?",
+        session_count,
+        stray_path.display(),
+        db_path.display()
+    )) {
+        let _ = set_setting(&ignore_key, "true", conn);
+        println!("{} will not be offered again", stray_path.display());
+        return;
+    }
+    // the stray database may be from an older version of punch
+    if let Err(err) = run_migrations(&source) {
+        exit_with_error(ErrorCode::CommandFailed, err);
+    }
+    let report = merge_database(&source, conn)
+        .unwrap_or_else(|err| exit_with_error(ErrorCode::CommandFailed, err));
+    drop(source);
+    // keeps the backups of earlier merges
+    let mut merged_path = stray_path.with_extension("db.merged");
+    let mut backup_count = 1;
+    while merged_path.exists() {
+        merged_path = stray_path.with_extension(format!("db.merged.{}", backup_count));
+        backup_count += 1;
+    }
+    if let Err(err) = fs::rename(&stray_path, &merged_path) {
+        exit_with_error(ErrorCode::CommandFailed, err);
+    }
+    show_merge(&report, &merged_path);
+}
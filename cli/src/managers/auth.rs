@@ -1,15 +1,55 @@
+use std::cell::{Cell, RefCell};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Deserialize;
+
 use super::{configs::AppConfigs, keyring::SecretsManager};
 
 use crate::api::{
     api::{fetch_access_token, verify_access_token},
+    client::ApiError,
     github::{fetch_gh_client_id, fetch_gh_login_info, prompt_and_fetch_gh_tokens},
 };
 use crate::utils::errors::SimpleError;
 
+// tokens this close to their expiry are checked with the server, it may have
+// revoked them or its clock may disagree
+const TOKEN_EXPIRY_MARGIN: u64 = 24 * 60 * 60;
+
+#[derive(Deserialize)]
+struct TokenClaims {
+    exp: u64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TokenState {
+    LoggedOut,
+    Valid,
+    Expired,
+    Invalid,
+    // the server could not be asked
+    Unverified,
+}
+
+impl TokenState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenState::LoggedOut => "logged out",
+            TokenState::Valid => "valid",
+            TokenState::Expired => "expired",
+            TokenState::Invalid => "invalid",
+            TokenState::Unverified => "unverified",
+        }
+    }
+}
+
 pub struct AuthManager<'a, T: SecretsManager> {
     configs: &'a AppConfigs,
     keyring_manager: &'a T,
-    token: Option<String>,
+    // the keyring is only read by the first command that needs the token
+    token: RefCell<Option<String>>,
+    is_loaded: Cell<bool>,
 }
 
 impl<'a, T> AuthManager<'a, T>
@@ -17,40 +57,83 @@ where
     T: SecretsManager,
 {
     pub fn new(configs: &'a AppConfigs, keyring_manager: &'a T) -> AuthManager<'a, T> {
-        match keyring_manager.retrieve_secrets() {
-            Ok(secret) => {
-                let endpoint = format!("{}/auth/verify", &configs.api_endpoint);
-                match verify_access_token(&endpoint, &secret) {
-                    Ok(is_verified) => AuthManager {
-                        configs,
-                        keyring_manager,
-                        token: if is_verified { Some(secret) } else { None },
-                    },
-                    Err(_) => AuthManager {
-                        configs,
-                        keyring_manager,
-                        token: None,
-                    },
+        AuthManager {
+            configs,
+            keyring_manager,
+            token: RefCell::new(None),
+            is_loaded: Cell::new(false),
+        }
+    }
+
+    fn load_token(&self) -> Option<String> {
+        let secret = self.keyring_manager.retrieve_secrets().ok()?;
+        match self.check_token(&secret) {
+            // the server could not be reached, keep the token so that
+            // punches can be queued and replayed once it is back.
+            TokenState::Valid | TokenState::Unverified => Some(secret),
+            _ => None,
+        }
+    }
+
+    fn check_token(&self, token: &str) -> TokenState {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs())
+            .unwrap_or_default();
+        match token_expiry(token) {
+            Some(expires_at) if expires_at <= now => TokenState::Expired,
+            Some(expires_at) if expires_at - now > TOKEN_EXPIRY_MARGIN => TokenState::Valid,
+            _ => {
+                let endpoint = format!("{}/auth/verify", &self.configs.api_endpoint);
+                match verify_access_token(&endpoint, token) {
+                    Ok(true) => TokenState::Valid,
+                    Ok(false) => TokenState::Invalid,
+                    Err(_) => TokenState::Unverified,
                 }
             }
-            Err(_) => AuthManager {
-                configs,
-                keyring_manager,
-                token: None,
-            },
+        }
+    }
+
+    // reads the keyring again, the token in use may have been dropped already
+    pub fn token_state(&self) -> TokenState {
+        match self.keyring_manager.retrieve_secrets() {
+            Ok(secret) => self.check_token(&secret),
+            Err(_) => TokenState::LoggedOut,
+        }
+    }
+
+    // the server answered 401, the token is verified once more and forgotten
+    // if it is no longer valid. the returned message is meant for the user.
+    pub fn reject_token(&self, message: String) -> String {
+        let token = match self.get_access_token() {
+            Some(token) => token,
+            None => return message,
+        };
+        let endpoint = format!("{}/auth/verify", &self.configs.api_endpoint);
+        match verify_access_token(&endpoint, &token) {
+            Ok(false) => {
+                self.keyring_manager.remove_secret();
+                self.token.replace(None);
+                String::from("the session has expired, log in again with punch login")
+            }
+            Ok(true) => message,
+            Err(err) => format!("failed to verify the token {}", err),
         }
     }
 
     // TODO: change the return type to &str
     pub fn get_access_token(&self) -> Option<String> {
-        self.token.clone()
+        if !self.is_loaded.replace(true) {
+            self.token.replace(self.load_token());
+        }
+        self.token.borrow().clone()
     }
 
-    pub fn login(&self) -> String {
+    pub fn login(&self) -> Result<String, ApiError> {
         let client_id_info =
-            fetch_gh_client_id(&self.configs.api_endpoint, &self.configs.gh_auth_scope);
-        let login_info = fetch_gh_login_info(&client_id_info);
-        let user = prompt_and_fetch_gh_tokens(&client_id_info, &login_info);
+            fetch_gh_client_id(&self.configs.api_endpoint, &self.configs.gh_auth_scope)?;
+        let login_info = fetch_gh_login_info(&client_id_info)?;
+        let user = prompt_and_fetch_gh_tokens(&client_id_info, &login_info)?;
         let token_endpoint = format!("{}/auth/login", &self.configs.api_endpoint);
         fetch_access_token(&token_endpoint, &user.access_token)
     }
@@ -76,3 +159,13 @@ where
         };
     }
 }
+
+// the expiry is read without checking the signature, only the server can tell
+// whether the token is genuine
+pub fn token_expiry(token: &str) -> Option<u64> {
+    let payload = token.split('.').nth(1)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    serde_json::from_slice::<TokenClaims>(&payload)
+        .ok()
+        .map(|claims| claims.exp)
+}
@@ -1,32 +1,250 @@
 extern crate dotenv;
 
-use dotenv::dotenv;
+use std::collections::BTreeMap;
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono_tz::Tz;
+use dotenv::dotenv;
+use reqwest::Proxy;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PROFILE: &str = "default";
+pub const DEFAULT_PROFILE_KEY: &str = "default_profile";
+pub const PROFILE_KEYS: [&str; 7] = [
+    "endpoint",
+    "scope",
+    "output",
+    "timezone",
+    "proxy",
+    "ca_bundle",
+    "timeout",
+];
+pub const CONFIG_KEYS: [&str; 8] = [
+    "endpoint",
+    "scope",
+    "output",
+    "timezone",
+    "proxy",
+    "ca_bundle",
+    "timeout",
+    DEFAULT_PROFILE_KEY,
+];
+
+const DEFAULT_GH_AUTH_SCOPE: &str = "read:user";
 
 pub struct AppConfigs {
+    pub profile: String,
     pub api_endpoint: String,
     pub gh_auth_scope: String,
+    pub output: Option<String>,
+    pub timezone: Option<String>,
+    pub proxy: Option<String>,
+    pub ca_bundle: Option<String>,
+    // seconds a request may take
+    pub timeout: Option<u64>,
 }
 
-pub fn fetch_configs() -> AppConfigs {
-    dotenv().ok();
-    let mut api_endpoint = String::new();
-    let mut gh_auth_scope = String::new();
-    for (key, value) in env::vars() {
-        if key == "API_ENDPOINT" {
-            api_endpoint = value;
-            continue;
+impl AppConfigs {
+    // the values in use once the environment had its say
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        match key {
+            "endpoint" => Ok(Some(self.api_endpoint.to_owned()).filter(|value| !value.is_empty())),
+            "scope" => Ok(Some(self.gh_auth_scope.to_owned())),
+            "output" => Ok(self.output.to_owned()),
+            "timezone" => Ok(self.timezone.to_owned()),
+            "proxy" => Ok(self.proxy.to_owned()),
+            "ca_bundle" => Ok(self.ca_bundle.to_owned()),
+            "timeout" => Ok(self.timeout.map(|timeout| timeout.to_string())),
+            _ => Err(unknown_key(key)),
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+impl Profile {
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let value = match key {
+            "endpoint" => &self.endpoint,
+            "scope" => &self.scope,
+            "output" => &self.output,
+            "timezone" => &self.timezone,
+            "proxy" => &self.proxy,
+            "ca_bundle" => &self.ca_bundle,
+            "timeout" => return Ok(self.timeout.map(|timeout| timeout.to_string())),
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(value.to_owned())
+    }
+
+    // an empty value removes the key from the profile
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let value = match key {
+            "endpoint" => value.trim_end_matches('/'),
+            "output" if !value.is_empty() && value != "text" && value != "json" => {
+                return Err(String::from("the output must be either text or json"));
+            }
+            "timezone" if !value.is_empty() && value.parse::<Tz>().is_err() => {
+                return Err(format!("unknown time zone '{}'", value));
+            }
+            "proxy" if !value.is_empty() && Proxy::all(value).is_err() => {
+                return Err(format!("invalid proxy '{}'", value));
+            }
+            "ca_bundle" if !value.is_empty() && !Path::new(value).is_file() => {
+                return Err(format!("no such file '{}'", value));
+            }
+            "timeout" => {
+                self.timeout = match value {
+                    "" => None,
+                    value => match value.parse::<u64>() {
+                        Ok(timeout) if timeout > 0 => Some(timeout),
+                        _ => return Err(String::from("the timeout must be a number of seconds")),
+                    },
+                };
+                return Ok(());
+            }
+            _ => value,
+        };
+        let value = Some(value.to_owned()).filter(|value| !value.is_empty());
+        match key {
+            "endpoint" => self.endpoint = value,
+            "scope" => self.scope = value,
+            "output" => self.output = value,
+            "timezone" => self.timezone = value,
+            "proxy" => self.proxy = value,
+            "ca_bundle" => self.ca_bundle = value,
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(())
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct ConfigFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl ConfigFile {
+    // $XDG_CONFIG_HOME/punch-cli/config.toml on linux
+    pub fn path() -> Result<PathBuf, String> {
+        match dirs::config_dir() {
+            Some(dir) => Ok(dir.join("punch-cli").join("config.toml")),
+            None => Err(String::from("failed to find the configuration directory")),
         }
-        if key == "GITHUB_AUTH_SCOPE" {
-            gh_auth_scope = value;
-            continue;
+    }
+
+    pub fn load() -> Result<ConfigFile, String> {
+        let path = ConfigFile::path()?;
+        if !path.exists() {
+            return Ok(ConfigFile::default());
         }
+        let content = fs::read_to_string(&path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        toml::from_str(&content)
+            .map_err(|err| format!("failed to parse {}: {}", path.display(), err))
     }
-    if api_endpoint.is_empty() || gh_auth_scope.is_empty() {
-        panic!("missing required environment variables")
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = ConfigFile::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("failed to create {}: {}", dir.display(), err))?;
+        }
+        let content = toml::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(&path, content)
+            .map_err(|err| format!("failed to write {}: {}", path.display(), err))
     }
-    AppConfigs {
-        api_endpoint,
-        gh_auth_scope,
+
+    pub fn active_profile(&self, profile: Option<&str>) -> String {
+        profile
+            .map(|profile| profile.to_owned())
+            .or_else(|| env_value("PUNCH_PROFILE"))
+            .or_else(|| self.default_profile.to_owned())
+            .unwrap_or_else(|| String::from(DEFAULT_PROFILE))
+    }
+}
+
+// the profile comes from --profile, PUNCH_PROFILE or the default_profile of
+// the file in that order. the variables of the environment, or of a .env file
+// in the current directory, win over the values of the profile.
+pub fn fetch_configs(profile: Option<&str>) -> Result<AppConfigs, String> {
+    dotenv().ok();
+    let file = ConfigFile::load()?;
+    let profile = file.active_profile(profile);
+    load_configs(&file, profile, true)
+}
+
+// the values of the file alone, as shown for every account side by side
+pub fn fetch_profile_configs(profile: &str) -> Result<AppConfigs, String> {
+    let file = ConfigFile::load()?;
+    load_configs(&file, profile.to_owned(), false)
+}
+
+fn load_configs(file: &ConfigFile, profile: String, with_env: bool) -> Result<AppConfigs, String> {
+    let settings = match file.profiles.get(&profile) {
+        Some(settings) => settings.to_owned(),
+        None if profile == DEFAULT_PROFILE => Profile::default(),
+        None => return Err(format!("unknown profile '{}'", profile)),
+    };
+    let env_override = |key: &str| env_value(key).filter(|_| with_env);
+    // the server and its scope only replace those of the default profile, the
+    // tokens of the other accounts are never sent to it
+    let server_override = |key: &str| env_override(key).filter(|_| profile == DEFAULT_PROFILE);
+    // TZ is set in many shells and containers, it only applies to the
+    // profiles without a timezone. chrono falls back to utc on a zone it
+    // does not know, so a typo is caught here.
+    let timezone = env_override("PUNCH_TIMEZONE").or(settings.timezone);
+    if let Some(time_zone) = timezone.as_ref().filter(|zone| zone.parse::<Tz>().is_err()) {
+        return Err(format!("unknown time zone '{}'", time_zone));
     }
+    Ok(AppConfigs {
+        api_endpoint: server_override("API_ENDPOINT")
+            .or(settings.endpoint)
+            .unwrap_or_default()
+            .trim_end_matches('/')
+            .to_owned(),
+        gh_auth_scope: server_override("GITHUB_AUTH_SCOPE")
+            .or(settings.scope)
+            .unwrap_or_else(|| String::from(DEFAULT_GH_AUTH_SCOPE)),
+        output: env_override("PUNCH_OUTPUT").or(settings.output),
+        timezone,
+        proxy: settings.proxy,
+        ca_bundle: settings.ca_bundle,
+        timeout: settings.timeout,
+        profile,
+    })
+}
+
+fn env_value(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|value| !value.is_empty())
+}
+
+fn unknown_key(key: &str) -> String {
+    format!(
+        "unknown configuration key '{}', use one of {}",
+        key,
+        PROFILE_KEYS.join(", ")
+    )
 }
@@ -1,5 +1,7 @@
 use keyring::{Entry, Error, Result};
 
+use super::configs::DEFAULT_PROFILE;
+
 pub trait SecretsManager {
     fn remove_secret(&self);
     fn retrieve_secrets(&self) -> Result<String>;
@@ -34,7 +36,25 @@ impl SecretsManager for KeyRingManager {
     }
 }
 
-pub fn new_key_ring_manager() -> impl SecretsManager {
-    let storage = Entry::new("punch-cli", "session_info").expect("failed to read keyring");
+// every profile is logged in on its own, the default one keeps the entry it
+// had before there were profiles
+pub fn new_key_ring_manager(profile: &str) -> impl SecretsManager {
+    let user = if profile == DEFAULT_PROFILE {
+        String::from("session_info")
+    } else {
+        format!("session_info:{}", profile)
+    };
+    let storage = Entry::new("punch-cli", &user).expect("failed to read keyring");
+    KeyRingManager { storage }
+}
+
+// the key of the encrypted databases of a profile
+pub fn new_database_key_manager(profile: &str) -> impl SecretsManager {
+    let user = if profile == DEFAULT_PROFILE {
+        String::from("database_key")
+    } else {
+        format!("database_key:{}", profile)
+    };
+    let storage = Entry::new("punch-cli", &user).expect("failed to read keyring");
     KeyRingManager { storage }
 }
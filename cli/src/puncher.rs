@@ -1,21 +1,59 @@
+use std::cell::Cell;
 use std::cmp;
+use std::collections::BTreeMap;
 
 use diesel::prelude::*;
 use diesel::sql_query;
+use diesel::sql_types::{BigInt, Text};
 use diesel::SqliteConnection;
+use urlencoding::encode;
 
-use crate::api::api::{cancel_task, get_task, list_task, start_task};
+use crate::api::{
+    api::{
+        add_task, cancel_task, finish_task, get_period_report, get_settings, get_task,
+        import_tasks, list_sessions, list_task, start_task, switch_task, update_session,
+        update_settings, APIImportedTask, APIUserSettings, ManualTaskPayload, SessionUpdatePayload,
+    },
+    client::ApiError,
+};
 use crate::database::{
-    schema::tasks::{self, finished_at, name, started_at, table},
-    task::{get_ts, get_unfinished_task, AggregatedTask, NewTask, Task},
+    aggregate::{
+        get_aggregate_task_names, get_aggregates_to_upload, get_task_aggregates,
+        mark_aggregates_uploaded, TaskAggregate,
+    },
+    schema::{
+        task_tags,
+        tasks::{self, finished_at, name, started_at, table},
+    },
+    sealed::{seal_text, sealed},
+    settings::{get_setting, set_setting, TASK_POLICY},
+    sync::{
+        add_to_cache, apply_to_cache, edit_in_cache, get_cached_task, get_cached_tasks,
+        get_pending_operations, queue_operation, remove_pending_operation, replace_cached_tasks,
+        CachedTask, NewPendingOperation, PendingOperation, PUNCH_ADD, PUNCH_CANCEL, PUNCH_IN,
+        PUNCH_OUT, PUNCH_SWITCH,
+    },
+    tag::{add_task_tags, get_tags_by_task_name, get_task_tags, normalize_tags},
+    task::{
+        finish_unfinished_tasks, get_overlapping_tasks, get_task_by_id, get_task_names,
+        get_task_sessions, get_tasks_to_upload, get_ts, get_unfinished_task, mark_tasks_uploaded,
+        session_exists, AggregatedTask, NewTask, Task,
+    },
 };
 use crate::managers::{auth::AuthManager, configs::AppConfigs, keyring::SecretsManager};
+use crate::utils::{
+    import::ImportedSession,
+    report::{local_time_zone_name, split_into_periods, ReportPeriod, ReportRow},
+    tree::{is_under_prefix, task_prefix},
+};
 
 pub struct TaskListItem {
     pub name: String,
     pub duration: i64,
     pub started_at: i64,
     pub finished_at: Option<i64>,
+    pub tags: Vec<String>,
+    pub pomodoros: i64,
 }
 
 pub type TaskList = Vec<TaskListItem>;
@@ -24,12 +62,84 @@ pub struct TaskStat {
     pub name: String,
     pub status: String,
     pub duration: i64,
+    pub pomodoros: i64,
+}
+
+pub struct TaskSession {
+    pub id: i64,
+    pub name: String,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+}
+
+pub struct SessionFilter {
+    pub task_name: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<usize>,
+    pub oldest_first: bool,
+}
+
+pub struct SyncReport {
+    pub synced: usize,
+    pub pending: usize,
+    pub rejected: Vec<String>,
 }
 
+pub struct UploadReport {
+    pub uploaded: usize,
+    pub skipped: usize,
+    pub duration: i64,
+}
+
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: usize,
+    pub duration: i64,
+}
+
+// what happens when a task is punched in while another one is running
+#[derive(Clone, Copy, PartialEq)]
+pub enum TaskPolicy {
+    Concurrent,
+    Single,
+    AutoSwitch,
+}
+
+impl TaskPolicy {
+    pub fn parse(policy: &str) -> Result<TaskPolicy, String> {
+        match policy {
+            "concurrent" => Ok(TaskPolicy::Concurrent),
+            "single" => Ok(TaskPolicy::Single),
+            "auto-switch" => Ok(TaskPolicy::AutoSwitch),
+            _ => Err(format!("unknown task policy '{}'", policy)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            TaskPolicy::Concurrent => "concurrent",
+            TaskPolicy::Single => "single",
+            TaskPolicy::AutoSwitch => "auto-switch",
+        }
+    }
+}
+
+pub struct SwitchReport {
+    pub stopped: Vec<Task>,
+    pub started_at: i64,
+}
+
+const UPLOAD_BATCH_SIZE: usize = 500;
+const SESSION_PAGE_SIZE: usize = 100;
+
 pub struct Puncher<'a, T: SecretsManager> {
     auth_manager: &'a AuthManager<'a, T>,
     configs: &'a AppConfigs,
     db_conn: &'a SqliteConnection,
+    offline: Cell<bool>,
 }
 
 impl<'a, T> Puncher<'a, T>
@@ -45,35 +155,79 @@ where
             auth_manager,
             configs,
             db_conn,
+            offline: Cell::new(false),
         }
     }
 
-    pub fn punch_in(&self, task_name: String) -> Result<i64, String> {
+    pub fn punch_in(&self, task_name: String, tags: &[String]) -> Result<i64, String> {
+        let tags = normalize_tags(tags);
         match self.auth_manager.get_access_token() {
             Some(token) => {
-                let api_resp = start_task(
-                    format!("{}/punch/in", self.configs.api_endpoint),
-                    token,
-                    task_name,
-                );
-                return match api_resp {
-                    Ok(task) => Ok(task.started_at),
-                    Err(err) => Err(err.into()),
-                };
+                let task_name = task_name.to_lowercase();
+                let punched_at = get_ts().map_err(|err| err.to_string())?.as_secs() as i64;
+                if self.is_online() {
+                    let api_resp = start_task(
+                        format!("{}/punch/in", self.configs.api_endpoint),
+                        token,
+                        task_name.clone(),
+                        punched_at,
+                        tags.clone(),
+                    );
+                    match api_resp {
+                        Ok(task) => {
+                            for stopped in task.stopped.iter() {
+                                self.record_locally(
+                                    PUNCH_OUT,
+                                    &stopped.name,
+                                    stopped.finished_at.unwrap_or(task.started_at),
+                                    &[],
+                                    None,
+                                    false,
+                                )?;
+                            }
+                            self.record_locally(
+                                PUNCH_IN,
+                                &task_name,
+                                task.started_at,
+                                &tags,
+                                None,
+                                false,
+                            )?;
+                            return Ok(task.started_at);
+                        }
+                        Err(ApiError::Network(_)) => self.offline.set(true),
+                        Err(err) => return Err(self.rejected(err)),
+                    };
+                }
+                let unfinished = get_unfinished_task(task_name.as_str(), self.db_conn);
+                let cached = get_cached_task(task_name.as_str(), self.db_conn);
+                if !unfinished.is_empty()
+                    || matches!(cached, Some(task) if task.finished_at.is_none())
+                {
+                    return Err(String::from("the task is already in progress"));
+                }
+                if let Some(switched_at) = self.apply_task_policy(&task_name, &tags)? {
+                    return Ok(switched_at);
+                }
+                self.queue(PUNCH_IN, &task_name, punched_at, &tags, None, false)?;
+                return Ok(punched_at);
             }
             None => {
                 let unfinished = get_unfinished_task(task_name.as_str(), self.db_conn);
                 if unfinished.len() > 0 {
                     return Err(String::from("the task is already in progress"));
                 }
+                if let Some(switched_at) = self.apply_task_policy(&task_name, &tags)? {
+                    return Ok(switched_at);
+                }
                 let new_task = NewTask {
                     name: task_name,
                     started_at: get_ts().unwrap().as_secs() as i64,
+                    finished_at: None,
+                    uploaded: false,
+                    note: None,
                 };
-                return match diesel::insert_into(table)
-                    .values(&new_task)
-                    .execute(self.db_conn)
-                {
+                return match self.insert_task(&new_task, &tags) {
                     Ok(_) => Ok(new_task.started_at),
                     Err(err) => Err(format!("{}", err)),
                 };
@@ -81,23 +235,79 @@ where
         }
     }
 
-    pub fn punch_out(&self, task_name: String) -> Result<Task, String> {
+    // sessions finished by a timer that ran its whole interval count as pomodoros
+    pub fn punch_out(
+        &self,
+        task_name: String,
+        note: Option<String>,
+        pomodoro: bool,
+    ) -> Result<Task, String> {
         match self.auth_manager.get_access_token() {
             Some(token) => {
-                let api_resp = start_task(
-                    format!("{}/punch/out", self.configs.api_endpoint),
-                    token,
-                    task_name,
-                );
-                return match api_resp {
-                    Ok(task) => Ok(Task {
-                        id: task.id as i32,
-                        name: task.name,
-                        started_at: task.started_at,
-                        finished_at: task.finished_at,
-                    }),
-                    Err(err) => Err(err.into()),
+                let task_name = task_name.to_lowercase();
+                let punched_at = get_ts().map_err(|err| err.to_string())?.as_secs() as i64;
+                if self.is_online() {
+                    let api_resp = finish_task(
+                        format!("{}/punch/out", self.configs.api_endpoint),
+                        token,
+                        task_name.clone(),
+                        punched_at,
+                        note.clone(),
+                        pomodoro,
+                    );
+                    match api_resp {
+                        Ok(task) => {
+                            self.record_locally(
+                                PUNCH_OUT,
+                                &task_name,
+                                task.finished_at.unwrap_or(punched_at),
+                                &[],
+                                note.as_deref(),
+                                pomodoro,
+                            )?;
+                            return Ok(Task {
+                                id: task.id as i32,
+                                name: task.name,
+                                started_at: task.started_at,
+                                finished_at: task.finished_at,
+                                uploaded: true,
+                                note: task.note,
+                                pomodoro: task.pomodoro,
+                            });
+                        }
+                        Err(ApiError::Network(_)) => self.offline.set(true),
+                        Err(err) => return Err(self.rejected(err)),
+                    };
+                }
+                let mut existing = get_unfinished_task(task_name.as_str(), self.db_conn);
+                let mut task = if !existing.is_empty() {
+                    existing.remove(0)
+                } else {
+                    match get_cached_task(task_name.as_str(), self.db_conn) {
+                        Some(cached) if cached.finished_at.is_none() => Task {
+                            id: 0,
+                            name: cached.name,
+                            started_at: cached.started_at,
+                            finished_at: None,
+                            uploaded: true,
+                            note: None,
+                            pomodoro: false,
+                        },
+                        _ => return Err(String::from("no task in progress")),
+                    }
                 };
+                self.queue(
+                    PUNCH_OUT,
+                    &task_name,
+                    punched_at,
+                    &[],
+                    note.as_deref(),
+                    pomodoro,
+                )?;
+                task.finished_at = Some(punched_at);
+                task.note = note;
+                task.pomodoro = pomodoro;
+                return Ok(task);
             }
             None => {
                 let mut existing = get_unfinished_task(task_name.as_str(), self.db_conn);
@@ -109,12 +319,18 @@ where
                     Err(err) => return Err(err.to_string()),
                 };
                 return match diesel::update(table.find(existing[0].id))
-                    .set(finished_at.eq(finished_ts))
+                    .set((
+                        finished_at.eq(finished_ts),
+                        tasks::note.eq(note.as_ref().map(sealed)),
+                        tasks::pomodoro.eq(pomodoro),
+                    ))
                     .execute(self.db_conn)
                 {
                     Ok(_) => {
                         let mut old_task = existing.remove(0);
                         old_task.finished_at = Some(finished_ts);
+                        old_task.note = note;
+                        old_task.pomodoro = pomodoro;
                         Ok(old_task)
                     }
                     Err(err) => Err(format!("{}", err)),
@@ -126,15 +342,36 @@ where
     pub fn cancel(&self, task_name: String) -> Result<(), String> {
         match self.auth_manager.get_access_token() {
             Some(token) => {
-                let api_resp = cancel_task(
-                    format!("{}/punch/cancel", self.configs.api_endpoint),
-                    token,
-                    task_name,
-                );
-                return match api_resp {
-                    Ok(_) => Ok(()),
-                    Err(err) => Err(format!("{}", err)),
-                };
+                let task_name = task_name.to_lowercase();
+                let punched_at = get_ts().map_err(|err| err.to_string())?.as_secs() as i64;
+                if self.is_online() {
+                    let api_resp = cancel_task(
+                        format!("{}/punch/cancel", self.configs.api_endpoint),
+                        token,
+                        task_name.clone(),
+                    );
+                    match api_resp {
+                        Ok(_) => {
+                            return self.record_locally(
+                                PUNCH_CANCEL,
+                                &task_name,
+                                punched_at,
+                                &[],
+                                None,
+                                false,
+                            )
+                        }
+                        Err(ApiError::Network(_)) => self.offline.set(true),
+                        Err(err) => return Err(self.rejected(err)),
+                    };
+                }
+                let started = get_unfinished_task(task_name.as_str(), self.db_conn);
+                let cached = get_cached_task(task_name.as_str(), self.db_conn);
+                if started.is_empty() && !matches!(cached, Some(task) if task.finished_at.is_none())
+                {
+                    return Err(String::from("no task in progress"));
+                }
+                return self.queue(PUNCH_CANCEL, &task_name, punched_at, &[], None, false);
             }
             None => {
                 let started = get_unfinished_task(task_name.as_str(), self.db_conn);
@@ -149,16 +386,355 @@ where
         }
     }
 
-    pub fn get(&self, task_name: String, since: i64, until: i64) -> Result<TaskStat, String> {
+    // logged in users share the policy across machines, the local copy only
+    // serves punches made while the server is unreachable.
+    pub fn task_policy(&self) -> Result<TaskPolicy, String> {
+        if let Some(token) = self.auth_manager.get_access_token() {
+            match get_settings(&format!("{}/settings", self.configs.api_endpoint), &token) {
+                Ok(settings) => {
+                    set_setting(TASK_POLICY, &settings.task_policy, self.db_conn)
+                        .map_err(|err| err.to_string())?;
+                }
+                Err(ApiError::Network(_)) => self.offline.set(true),
+                Err(err) => return Err(self.rejected(err)),
+            };
+        }
+        Ok(self.cached_task_policy())
+    }
+
+    pub fn set_task_policy(&self, policy: TaskPolicy) -> Result<(), String> {
+        if let Some(token) = self.auth_manager.get_access_token() {
+            let api_resp = update_settings(
+                &format!("{}/settings", self.configs.api_endpoint),
+                &token,
+                APIUserSettings {
+                    task_policy: policy.as_str().to_owned(),
+                },
+            );
+            match api_resp {
+                Ok(_) => {}
+                Err(ApiError::Network(_)) => {
+                    self.offline.set(true);
+                    return Err(String::from("changing the policy requires the server"));
+                }
+                Err(err) => return Err(self.rejected(err)),
+            };
+        }
+        set_setting(TASK_POLICY, policy.as_str(), self.db_conn)
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    // stops whatever is running and starts the new task at the same instant
+    pub fn switch(&self, task_name: String, tags: &[String]) -> Result<SwitchReport, String> {
+        let tags = normalize_tags(tags);
+        let punched_at = get_ts().map_err(|err| err.to_string())?.as_secs() as i64;
+        match self.auth_manager.get_access_token() {
+            Some(token) => {
+                let task_name = task_name.to_lowercase();
+                if self.is_online() {
+                    let api_resp = switch_task(
+                        format!("{}/punch/switch", self.configs.api_endpoint),
+                        token,
+                        task_name.clone(),
+                        punched_at,
+                        tags.clone(),
+                    );
+                    match api_resp {
+                        Ok(switched) => {
+                            self.record_switch_locally(
+                                &task_name,
+                                switched.started.started_at,
+                                &tags,
+                            )?;
+                            return Ok(SwitchReport {
+                                stopped: switched
+                                    .stopped
+                                    .into_iter()
+                                    .map(|task| Task {
+                                        id: task.id as i32,
+                                        name: task.name,
+                                        started_at: task.started_at,
+                                        finished_at: task.finished_at,
+                                        uploaded: true,
+                                        note: task.note,
+                                        pomodoro: task.pomodoro,
+                                    })
+                                    .collect(),
+                                started_at: switched.started.started_at,
+                            });
+                        }
+                        Err(ApiError::Network(_)) => self.offline.set(true),
+                        Err(err) => return Err(self.rejected(err)),
+                    };
+                }
+                let unfinished = get_unfinished_task(task_name.as_str(), self.db_conn);
+                let cached = get_cached_task(task_name.as_str(), self.db_conn);
+                if !unfinished.is_empty()
+                    || matches!(cached, Some(task) if task.finished_at.is_none())
+                {
+                    return Err(String::from("the task is already in progress"));
+                }
+                let operation = NewPendingOperation {
+                    kind: PUNCH_SWITCH.to_owned(),
+                    task_name: task_name.clone(),
+                    timestamp: punched_at,
+                    tags: tags.join(","),
+                    note: None,
+                    finished_at: None,
+                    force: false,
+                    pomodoro: false,
+                };
+                let stopped = self
+                    .db_conn
+                    .transaction::<_, diesel::result::Error, _>(|| {
+                        queue_operation(&operation, self.db_conn)?;
+                        self.switch_locally(&task_name, punched_at, &tags, true)
+                    })
+                    .map_err(|err| err.to_string())?;
+                Ok(SwitchReport {
+                    stopped,
+                    started_at: punched_at,
+                })
+            }
+            None => {
+                let unfinished = get_unfinished_task(task_name.as_str(), self.db_conn);
+                if !unfinished.is_empty() {
+                    return Err(String::from("the task is already in progress"));
+                }
+                let stopped = self
+                    .db_conn
+                    .transaction(|| self.switch_locally(&task_name, punched_at, &tags, false))
+                    .map_err(|err: diesel::result::Error| err.to_string())?;
+                Ok(SwitchReport {
+                    stopped,
+                    started_at: punched_at,
+                })
+            }
+        }
+    }
+
+    pub fn add(
+        &self,
+        task_name: String,
+        from_ts: i64,
+        to_ts: i64,
+        tags: &[String],
+        note: Option<String>,
+        force: bool,
+    ) -> Result<Task, String> {
+        let tags = normalize_tags(tags);
+        let now_ts = get_ts().map_err(|err| err.to_string())?.as_secs() as i64;
+        validate_session_range(from_ts, Some(to_ts), now_ts)?;
+        match self.auth_manager.get_access_token() {
+            Some(token) => {
+                let task_name = task_name.to_lowercase();
+                if self.is_online() {
+                    let api_resp = add_task(
+                        &format!("{}/punch/add", self.configs.api_endpoint),
+                        &token,
+                        ManualTaskPayload {
+                            name: task_name.clone(),
+                            started_at: from_ts,
+                            finished_at: to_ts,
+                            tags: tags.clone(),
+                            note: note.clone(),
+                            force,
+                        },
+                    );
+                    match api_resp {
+                        Ok(task) => {
+                            self.record_added_locally(&task_name, from_ts, to_ts, &tags, &note)?;
+                            return Ok(Task {
+                                id: task.id as i32,
+                                name: task.name,
+                                started_at: task.started_at,
+                                finished_at: task.finished_at,
+                                uploaded: true,
+                                note: task.note,
+                                pomodoro: task.pomodoro,
+                            });
+                        }
+                        Err(ApiError::Network(_)) => self.offline.set(true),
+                        Err(err) => return Err(self.rejected(err)),
+                    };
+                }
+                // the server checks again for overlaps once the entry is replayed
+                if !force {
+                    self.check_overlaps(&task_name, from_ts, to_ts, None)?;
+                }
+                let operation = NewPendingOperation {
+                    kind: PUNCH_ADD.to_owned(),
+                    task_name: task_name.clone(),
+                    timestamp: from_ts,
+                    tags: tags.join(","),
+                    note: note.clone(),
+                    finished_at: Some(to_ts),
+                    force,
+                    pomodoro: false,
+                };
+                if let Err(err) = queue_operation(&operation, self.db_conn) {
+                    return Err(format!("{}", err));
+                }
+                let task_id =
+                    self.record_added_locally(&task_name, from_ts, to_ts, &tags, &note)?;
+                return Ok(Task {
+                    id: task_id,
+                    name: task_name,
+                    started_at: from_ts,
+                    finished_at: Some(to_ts),
+                    uploaded: true,
+                    note,
+                    pomodoro: false,
+                });
+            }
+            None => {
+                if !force {
+                    self.check_overlaps(&task_name, from_ts, to_ts, None)?;
+                }
+                let new_task = NewTask {
+                    name: task_name,
+                    started_at: from_ts,
+                    finished_at: Some(to_ts),
+                    uploaded: false,
+                    note,
+                };
+                return match self.insert_task(&new_task, &tags) {
+                    Ok(task_id) => Ok(Task {
+                        id: task_id,
+                        name: new_task.name,
+                        started_at: new_task.started_at,
+                        finished_at: new_task.finished_at,
+                        uploaded: false,
+                        note: new_task.note,
+                        pomodoro: false,
+                    }),
+                    Err(err) => Err(format!("{}", err)),
+                };
+            }
+        }
+    }
+
+    pub fn edit(
+        &self,
+        session_id: i64,
+        from_ts: Option<i64>,
+        to_ts: Option<i64>,
+        force: bool,
+    ) -> Result<Task, String> {
+        let now_ts = get_ts().map_err(|err| err.to_string())?.as_secs() as i64;
+        match self.auth_manager.get_access_token() {
+            Some(token) => {
+                // session ids belong to the server, so there is nothing to queue.
+                // the edit waits for the queued punches it may depend on.
+                if self.is_online() {
+                    let api_resp = update_session(
+                        &format!(
+                            "{}/punch/sessions/{}",
+                            self.configs.api_endpoint, session_id
+                        ),
+                        &token,
+                        SessionUpdatePayload {
+                            started_at: from_ts,
+                            finished_at: to_ts,
+                            force,
+                        },
+                    );
+                    match api_resp {
+                        Ok(task) => {
+                            self.record_edit_locally(
+                                &task.name,
+                                (
+                                    task.previous_started_at.unwrap_or(task.started_at),
+                                    task.previous_finished_at,
+                                ),
+                                (task.started_at, task.finished_at),
+                            )?;
+                            return Ok(Task {
+                                id: task.id as i32,
+                                name: task.name,
+                                started_at: task.started_at,
+                                finished_at: task.finished_at,
+                                uploaded: true,
+                                note: task.note,
+                                pomodoro: task.pomodoro,
+                            });
+                        }
+                        Err(ApiError::Network(_)) => self.offline.set(true),
+                        Err(err) => return Err(self.rejected(err)),
+                    };
+                }
+                return Err(String::from("editing a session requires the server"));
+            }
+            None => {
+                let mut task = match get_task_by_id(session_id as i32, self.db_conn) {
+                    Ok(Some(task)) => task,
+                    Ok(None) => return Err(String::from("no session with the given id found")),
+                    Err(err) => return Err(format!("{}", err)),
+                };
+                task.started_at = from_ts.unwrap_or(task.started_at);
+                task.finished_at = to_ts.or(task.finished_at);
+                validate_session_range(task.started_at, task.finished_at, now_ts)?;
+                if !force {
+                    self.check_overlaps(
+                        &task.name,
+                        task.started_at,
+                        task.finished_at.unwrap_or(now_ts),
+                        Some(task.id),
+                    )?;
+                }
+                return match diesel::update(table.find(task.id))
+                    .set((
+                        started_at.eq(task.started_at),
+                        finished_at.eq(task.finished_at),
+                    ))
+                    .execute(self.db_conn)
+                {
+                    Ok(_) => Ok(task),
+                    Err(err) => Err(format!("{}", err)),
+                };
+            }
+        }
+    }
+
+    pub fn get(
+        &self,
+        task_name: Option<String>,
+        since: i64,
+        until: i64,
+        tags: &[String],
+    ) -> Result<TaskStat, String> {
+        let tags = normalize_tags(tags);
+        let report_name = match &task_name {
+            Some(task_name) => task_name.to_owned(),
+            None if !tags.is_empty() => tags
+                .iter()
+                .map(|tag| format!("+{}", tag))
+                .collect::<Vec<String>>()
+                .join(" "),
+            None => return Err(String::from("you must provide the task name or a tag")),
+        };
         match self.auth_manager.get_access_token() {
             Some(token) => {
+                if self.offline.get() {
+                    return self.get_cached(task_name, since, until, &tags);
+                }
+                let (path, prefix) = match &task_name {
+                    Some(task_name) => match task_prefix(task_name) {
+                        Some(prefix) => (String::new(), prefix.to_owned()),
+                        None => (format!("/{}", encode(task_name)), String::new()),
+                    },
+                    None => (String::new(), String::new()),
+                };
                 let api_resp = get_task(
                     format!(
-                        "{}/punch/get/{}?since={}&until={}",
+                        "{}/punch/get{}?since={}&until={}&tags={}&prefix={}",
                         self.configs.api_endpoint,
-                        task_name,
-                        since.to_string(),
-                        until.to_string(),
+                        path,
+                        since,
+                        until,
+                        encode(&tags.join(",")),
+                        encode(&prefix),
                     ),
                     token,
                 );
@@ -167,23 +743,64 @@ where
                         name: task_stat.name,
                         status: task_stat.status,
                         duration: task_stat.duration,
+                        pomodoros: task_stat.pomodoros,
                     }),
-                    Err(err) => Err(format!("{}", err)),
+                    Err(ApiError::Network(_)) => {
+                        self.offline.set(true);
+                        self.get_cached(task_name, since, until, &tags)
+                    }
+                    Err(err) => Err(self.rejected(err)),
                 };
             }
             None => {
-                return match tasks::table
-                    .filter(name.eq(task_name.clone()))
-                    .filter(finished_at.ge(since))
-                    .or_filter(finished_at.is_null())
+                let mut query = tasks::table
+                    .filter(finished_at.ge(since).or(finished_at.is_null()))
                     .filter(started_at.le(until))
                     .order(started_at.asc())
-                    .load::<Task>(self.db_conn)
-                {
+                    .into_boxed();
+                // sealed names cannot be matched by their start in sqlite,
+                // the tasks under a prefix are picked once loaded
+                let prefix = task_name.as_deref().and_then(task_prefix);
+                if let (Some(task_name), None) = (&task_name, prefix) {
+                    query = query.filter(name.eq(sealed(task_name)));
+                }
+                for tag in tags.iter() {
+                    query = query.filter(
+                        tasks::id.eq_any(
+                            task_tags::table
+                                .filter(task_tags::tag.eq(sealed(tag)))
+                                .select(task_tags::task_id),
+                        ),
+                    );
+                }
+                let exact_name = task_name.as_deref().filter(|_| prefix.is_none());
+                let is_picked = |task_name: &str| match prefix {
+                    Some(prefix) => is_under_prefix(task_name, prefix),
+                    None => true,
+                };
+                let aggregates: Vec<TaskAggregate> =
+                    get_task_aggregates(exact_name, since, until, self.db_conn)
+                        .map_err(|err| err.to_string())?
+                        .into_iter()
+                        .filter(|aggregate| is_picked(&aggregate.name))
+                        .filter(|aggregate| aggregate.has_tags(&tags))
+                        .collect();
+                return match query.load::<Task>(self.db_conn) {
                     Ok(tasks) => {
-                        if tasks.len() == 0 {
-                            return Err(format!("no task found for {}", task_name.to_owned()));
+                        let tasks: Vec<Task> = tasks
+                            .into_iter()
+                            .filter(|task| is_picked(&task.name))
+                            .collect();
+                        if tasks.len() == 0 && aggregates.is_empty() {
+                            return Err(format!("no task found for {}", report_name));
                         }
+                        let compacted: i64 = aggregates
+                            .iter()
+                            .map(|aggregate| {
+                                let (_, from_ts, to_ts) = aggregate.as_session();
+                                cmp::max(cmp::min(to_ts, until) - cmp::max(from_ts, since), 0)
+                            })
+                            .sum();
                         let sum: i64 = tasks
                             .iter()
                             .map(|task| match task.finished_at {
@@ -192,59 +809,1095 @@ where
                                 }
                                 None => until - cmp::max(task.started_at, since),
                             })
-                            .fold(0, |a, b| a + b);
+                            .fold(compacted, |a, b| a + b);
                         Ok(TaskStat {
-                            name: task_name.clone(),
+                            name: report_name,
                             status: if tasks.iter().any(|task| task.finished_at.is_none()) {
                                 "in progress".to_owned()
                             } else {
                                 "complete".to_owned()
                             },
                             duration: sum,
+                            pomodoros: tasks.iter().filter(|task| task.pomodoro).count() as i64
+                                + aggregates
+                                    .iter()
+                                    .map(|aggregate| aggregate.pomodoros)
+                                    .sum::<i64>(),
                         })
                     }
                     Err(err) => Err(format!("{}", err)),
-                }
+                };
             }
         }
     }
 
-    pub fn list(&self) -> Result<Vec<TaskListItem>, String> {
+    pub fn list(&self, tags: &[String]) -> Result<Vec<TaskListItem>, String> {
+        let tags = normalize_tags(tags);
         match self.auth_manager.get_access_token() {
             Some(token) => {
-                let endpoint = format!("{}/punch/list", self.configs.api_endpoint,);
-                let api_resp = list_task(&endpoint, &token);
-                return match api_resp {
-                    Ok(task_list) => Ok(task_list
+                if !self.offline.get() {
+                    let endpoint = format!(
+                        "{}/punch/list?tags={}",
+                        self.configs.api_endpoint,
+                        encode(&tags.join(",")),
+                    );
+                    match list_task(&endpoint, &token) {
+                        Ok(task_list) => {
+                            // only the unfiltered list is a faithful copy of the server data
+                            if tags.is_empty() {
+                                let cached: Vec<CachedTask> = task_list
+                                    .iter()
+                                    .map(|item| CachedTask {
+                                        name: item.name.to_owned(),
+                                        duration: item.duration,
+                                        started_at: item.started_at,
+                                        finished_at: item.finished_at,
+                                        pomodoros: item.pomodoros,
+                                    })
+                                    .collect();
+                                if let Err(err) = replace_cached_tasks(&cached, self.db_conn) {
+                                    return Err(format!("{}", err));
+                                }
+                            }
+                            return Ok(task_list
+                                .iter()
+                                .map(|item| TaskListItem {
+                                    name: item.name.to_owned(),
+                                    started_at: item.started_at,
+                                    finished_at: item.finished_at,
+                                    duration: item.duration,
+                                    tags: item.tags.to_owned(),
+                                    pomodoros: item.pomodoros,
+                                })
+                                .collect());
+                        }
+                        Err(ApiError::Network(_)) => self.offline.set(true),
+                        Err(err) => return Err(self.rejected(err)),
+                    };
+                }
+                if !tags.is_empty() {
+                    return Err(String::from("filtering by tag requires the server"));
+                }
+                return match get_cached_tasks(self.db_conn) {
+                    Ok(tasks) => Ok(tasks
                         .iter()
-                        .map(|item| TaskListItem {
-                            name: item.name.to_owned(),
-                            started_at: item.started_at,
-                            finished_at: item.finished_at,
-                            duration: item.duration,
+                        .map(|task| TaskListItem {
+                            name: task.name.to_owned(),
+                            started_at: task.started_at,
+                            finished_at: task.finished_at,
+                            duration: task.duration,
+                            tags: vec![],
+                            pomodoros: task.pomodoros,
                         })
                         .collect()),
                     Err(err) => Err(format!("{}", err)),
                 };
             }
             None => {
+                // a session matches when it carries every one of the requested tags
                 let sqlite_op = sql_query(
-                    "SELECT name, max(started_at) as started_at, case when count(*) - count(finished_at) > 0 then null else max(finished_at) end as finished_at, coalesce(sum(finished_at - started_at), 0) as duration FROM tasks GROUP BY name;",
+                    "SELECT name, max(started_at) as started_at, case when count(*) - count(finished_at) > 0 then null else max(finished_at) end as finished_at, coalesce(sum(finished_at - started_at), 0) as duration, sum(pomodoro) as pomodoros FROM tasks WHERE (SELECT count(*) FROM task_tags WHERE task_id = tasks.id AND instr(',' || ? || ',', ',' || tag || ',') > 0) = ? GROUP BY name;",
                 )
+                    .bind::<Text, _>(
+                        tags.iter()
+                            .map(|tag| seal_text(tag))
+                            .collect::<Vec<String>>()
+                            .join(","),
+                    )
+                    .bind::<BigInt, _>(tags.len() as i64)
                     .load::<AggregatedTask>(self.db_conn);
+                let task_tags = get_tags_by_task_name(self.db_conn).unwrap_or_default();
+                let aggregates = get_task_aggregates(None, 0, i64::MAX, self.db_conn)
+                    .map_err(|err| err.to_string())?;
                 return match sqlite_op {
-                    Ok(tasks) => Ok(tasks
-                        .iter()
-                        .map(|task| TaskListItem {
-                            name: task.name.to_owned(),
-                            started_at: task.started_at,
-                            finished_at: task.finished_at,
-                            duration: task.duration,
-                        })
-                        .collect()),
+                    Ok(tasks) => {
+                        let mut items: BTreeMap<String, TaskListItem> = tasks
+                            .iter()
+                            .map(|task| {
+                                let item = TaskListItem {
+                                    name: task.name.to_owned(),
+                                    started_at: task.started_at,
+                                    finished_at: task.finished_at,
+                                    duration: task.duration,
+                                    tags: task_tags
+                                        .iter()
+                                        .filter(|(task_name, _)| *task_name == task.name)
+                                        .map(|(_, tag)| tag.to_owned())
+                                        .collect(),
+                                    pomodoros: task.pomodoros,
+                                };
+                                (task.name.to_owned(), item)
+                            })
+                            .collect();
+                        // the compacted days add up with the sessions left
+                        for aggregate in aggregates
+                            .iter()
+                            .filter(|aggregate| aggregate.has_tags(&tags))
+                        {
+                            let item =
+                                items.entry(aggregate.name.to_owned()).or_insert_with(|| {
+                                    TaskListItem {
+                                        name: aggregate.name.to_owned(),
+                                        started_at: aggregate.started_at,
+                                        finished_at: Some(aggregate.finished_at),
+                                        duration: 0,
+                                        tags: vec![],
+                                        pomodoros: 0,
+                                    }
+                                });
+                            item.started_at = cmp::max(item.started_at, aggregate.started_at);
+                            item.finished_at = item
+                                .finished_at
+                                .map(|finished| cmp::max(finished, aggregate.finished_at));
+                            item.duration += aggregate.duration;
+                            item.pomodoros += aggregate.pomodoros;
+                        }
+                        for aggregate in aggregates.iter() {
+                            if let Some(item) = items.get_mut(&aggregate.name) {
+                                item.tags.extend(aggregate.tag_list());
+                                item.tags.sort();
+                                item.tags.dedup();
+                            }
+                        }
+                        Ok(items.into_values().collect())
+                    }
                     Err(err) => Err(format!("{}", err)),
                 };
             }
         }
     }
+
+    pub fn sessions(&self, filter: &SessionFilter) -> Result<Vec<TaskSession>, String> {
+        let mut sessions = vec![];
+        self.stream_sessions(filter, |page| {
+            sessions.extend(page);
+            Ok(())
+        })?;
+        Ok(sessions)
+    }
+
+    // sessions are handed over a page at a time, so long histories never have
+    // to be held in memory at once
+    pub fn stream_sessions<F>(&self, filter: &SessionFilter, mut on_page: F) -> Result<(), String>
+    where
+        F: FnMut(Vec<TaskSession>) -> Result<(), String>,
+    {
+        match self.auth_manager.get_access_token() {
+            Some(token) => {
+                let task_name = filter
+                    .task_name
+                    .as_ref()
+                    .map(|task_name| task_name.to_lowercase());
+                if !self.offline.get() {
+                    match self.fetch_sessions(&token, &task_name, filter, &mut on_page) {
+                        Ok(_) => return Ok(()),
+                        Err(ApiError::Network(_)) => self.offline.set(true),
+                        Err(err) => return Err(self.rejected(err)),
+                    };
+                }
+                // the local mirror only holds the sessions punched on this machine
+                on_page(self.get_local_sessions(task_name.as_deref(), filter)?)
+            }
+            None => on_page(self.get_local_sessions(filter.task_name.as_deref(), filter)?),
+        }
+    }
+
+    pub fn report(
+        &self,
+        period: ReportPeriod,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<ReportRow>, String> {
+        match self.auth_manager.get_access_token() {
+            Some(token) => {
+                if !self.offline.get() {
+                    let endpoint = format!(
+                        "{}/punch/report?period={}&since={}&until={}&tz={}",
+                        self.configs.api_endpoint,
+                        period.as_str(),
+                        since,
+                        until,
+                        encode(&local_time_zone_name()),
+                    );
+                    match get_period_report(&endpoint, &token) {
+                        Ok(rows) => {
+                            return Ok(rows
+                                .into_iter()
+                                .map(|row| ReportRow {
+                                    period_start: row.period_start,
+                                    name: row.name,
+                                    duration: row.duration,
+                                })
+                                .collect())
+                        }
+                        Err(ApiError::Network(_)) => self.offline.set(true),
+                        Err(err) => return Err(self.rejected(err)),
+                    };
+                }
+                self.get_local_report(period, since, until)
+            }
+            None => self.get_local_report(period, since, until),
+        }
+    }
+
+    pub fn sync(&self) -> Result<SyncReport, String> {
+        let mut report = SyncReport {
+            synced: 0,
+            pending: 0,
+            rejected: vec![],
+        };
+        // the keyring is only read when there is something to replay
+        let operations = get_pending_operations(self.db_conn).map_err(|err| err.to_string())?;
+        if operations.is_empty() {
+            return Ok(report);
+        }
+        let token = match self.auth_manager.get_access_token() {
+            Some(token) => token,
+            None => return Ok(report),
+        };
+        for (idx, operation) in operations.iter().enumerate() {
+            let endpoint = format!("{}/punch/{}", self.configs.api_endpoint, operation.kind);
+            let task_name = operation.task_name.clone();
+            // only the first punch of the queue was ever sent, a timeout may
+            // have left it applied on the server
+            let is_applied = match idx {
+                0 => self.was_applied(&token, operation),
+                _ => Ok(false),
+            };
+            let replayed = is_applied.and_then(|is_applied| match operation.kind.as_str() {
+                _ if is_applied => Ok(()),
+                PUNCH_IN => start_task(
+                    endpoint,
+                    token.clone(),
+                    task_name,
+                    operation.timestamp,
+                    split_tags(&operation.tags),
+                )
+                .map(|_| ()),
+                PUNCH_OUT => finish_task(
+                    endpoint,
+                    token.clone(),
+                    task_name,
+                    operation.timestamp,
+                    operation.note.clone(),
+                    operation.pomodoro,
+                )
+                .map(|_| ()),
+                PUNCH_ADD => add_task(
+                    &endpoint,
+                    &token,
+                    ManualTaskPayload {
+                        name: task_name,
+                        started_at: operation.timestamp,
+                        finished_at: operation.finished_at.unwrap_or(operation.timestamp),
+                        tags: split_tags(&operation.tags),
+                        note: operation.note.clone(),
+                        force: operation.force,
+                    },
+                )
+                .map(|_| ()),
+                PUNCH_SWITCH => switch_task(
+                    endpoint,
+                    token.clone(),
+                    task_name,
+                    operation.timestamp,
+                    split_tags(&operation.tags),
+                )
+                .map(|_| ()),
+                _ => cancel_task(endpoint, token.clone(), task_name),
+            });
+            match replayed {
+                Ok(_) => report.synced += 1,
+                Err(ApiError::Network(_)) => {
+                    self.offline.set(true);
+                    report.pending = operations.len() - idx;
+                    break;
+                }
+                // the queue is kept for the next login
+                Err(ApiError::Auth(err)) => {
+                    return Err(self.auth_manager.reject_token(err));
+                }
+                Err(err) => report.rejected.push(format!(
+                    "punch {} {}: {}",
+                    operation.kind, operation.task_name, err
+                )),
+            };
+            remove_pending_operation(operation.id, self.db_conn).map_err(|err| err.to_string())?;
+        }
+        Ok(report)
+    }
+
+    // looks for the session a queued punch would leave behind, a cancelled
+    // task is applied once it no longer runs
+    fn was_applied(&self, token: &str, operation: &PendingOperation) -> Result<bool, ApiError> {
+        let timestamp = operation.timestamp;
+        let endpoint = format!(
+            "{}/punch/sessions?name={}&since={}&until={}&limit={}",
+            self.configs.api_endpoint,
+            encode(&operation.task_name),
+            timestamp,
+            timestamp,
+            SESSION_PAGE_SIZE,
+        );
+        let sessions = list_sessions(&endpoint, token)?;
+        Ok(match operation.kind.as_str() {
+            PUNCH_IN | PUNCH_SWITCH => sessions
+                .iter()
+                .any(|session| session.started_at == timestamp),
+            PUNCH_OUT => sessions
+                .iter()
+                .any(|session| session.finished_at == Some(timestamp)),
+            PUNCH_ADD => sessions.iter().any(|session| {
+                session.started_at == timestamp && session.finished_at == operation.finished_at
+            }),
+            _ => sessions.iter().all(|session| session.finished_at.is_some()),
+        })
+    }
+
+    // never asks the server, the cache is kept up to date by every punch
+    pub fn known_task_names(&self, in_progress_only: bool) -> Result<Vec<String>, String> {
+        match self.auth_manager.get_access_token() {
+            Some(_) => self.cached_task_names(in_progress_only),
+            None => self.local_task_names(in_progress_only),
+        }
+    }
+
+    // used by the completion scripts on every tab, so the keyring is left
+    // alone and both the cache and the local tables are suggested
+    pub fn completion_task_names(&self, in_progress_only: bool) -> Result<Vec<String>, String> {
+        let mut names = self.cached_task_names(in_progress_only)?;
+        names.extend(self.local_task_names(in_progress_only)?);
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    fn cached_task_names(&self, in_progress_only: bool) -> Result<Vec<String>, String> {
+        get_cached_tasks(self.db_conn)
+            .map(|tasks| {
+                tasks
+                    .into_iter()
+                    .filter(|task| !in_progress_only || task.finished_at.is_none())
+                    .map(|task| task.name)
+                    .collect()
+            })
+            .map_err(|err| err.to_string())
+    }
+
+    fn local_task_names(&self, in_progress_only: bool) -> Result<Vec<String>, String> {
+        let mut names =
+            get_task_names(in_progress_only, self.db_conn).map_err(|err| err.to_string())?;
+        if !in_progress_only {
+            names.extend(get_aggregate_task_names(self.db_conn).map_err(|err| err.to_string())?);
+            names.sort();
+            names.dedup();
+        }
+        Ok(names)
+    }
+
+    pub fn count_tasks_to_upload(&self) -> Result<usize, String> {
+        let tasks = get_tasks_to_upload(self.db_conn).map_err(|err| err.to_string())?;
+        let compacted: i64 = get_aggregates_to_upload(self.db_conn)
+            .map_err(|err| err.to_string())?
+            .iter()
+            .map(|aggregate| aggregate.sessions)
+            .sum();
+        Ok(tasks.len() + compacted as usize)
+    }
+
+    // sessions recorded before logging in only exist in the local database,
+    // so they are sent over in batches and flagged once the server has them.
+    pub fn upload_local_history(&self, token: &str) -> Result<UploadReport, String> {
+        let tasks = get_tasks_to_upload(self.db_conn).map_err(|err| err.to_string())?;
+        let endpoint = format!("{}/punch/import", self.configs.api_endpoint);
+        let mut report = UploadReport {
+            uploaded: 0,
+            skipped: 0,
+            duration: 0,
+        };
+        for batch in tasks.chunks(UPLOAD_BATCH_SIZE) {
+            let payload = batch
+                .iter()
+                .map(|task| APIImportedTask {
+                    name: task.name.to_owned(),
+                    started_at: task.started_at,
+                    finished_at: task.finished_at,
+                    tags: get_task_tags(task.id, self.db_conn).unwrap_or_default(),
+                    note: task.note.to_owned(),
+                    pomodoro: task.pomodoro,
+                })
+                .collect();
+            let imported =
+                import_tasks(&endpoint, token, payload).map_err(|err| err.to_string())?;
+            let task_ids: Vec<i32> = batch.iter().map(|task| task.id).collect();
+            mark_tasks_uploaded(&task_ids, self.db_conn).map_err(|err| err.to_string())?;
+            report.uploaded += imported.imported;
+            report.skipped += imported.skipped;
+            report.duration += imported.duration;
+        }
+        // the compacted days no longer know their sessions, each is sent as
+        // sessions from the start of the day, one after the other, one for
+        // every pomodoro so that those add up too.
+        let aggregates = get_aggregates_to_upload(self.db_conn).map_err(|err| err.to_string())?;
+        let mut day_starts: BTreeMap<(String, i64), i64> = BTreeMap::new();
+        for batch in aggregates.chunks(UPLOAD_BATCH_SIZE) {
+            let mut payload = vec![];
+            for aggregate in batch {
+                let piece_start = day_starts
+                    .entry((aggregate.name.to_owned(), aggregate.day))
+                    .or_insert(aggregate.day);
+                let pieces = cmp::max(aggregate.pomodoros, 1);
+                for piece in 0..pieces {
+                    let mut duration = aggregate.duration / pieces;
+                    if piece == pieces - 1 {
+                        duration += aggregate.duration % pieces;
+                    }
+                    payload.push(APIImportedTask {
+                        name: aggregate.name.to_owned(),
+                        started_at: *piece_start,
+                        finished_at: Some(*piece_start + duration),
+                        tags: aggregate.tag_list(),
+                        note: None,
+                        pomodoro: aggregate.pomodoros > 0,
+                    });
+                    *piece_start += duration;
+                }
+            }
+            let imported =
+                import_tasks(&endpoint, token, payload).map_err(|err| err.to_string())?;
+            mark_aggregates_uploaded(batch, self.db_conn).map_err(|err| err.to_string())?;
+            report.uploaded += imported.imported;
+            report.skipped += imported.skipped;
+            report.duration += imported.duration;
+        }
+        Ok(report)
+    }
+
+    // sessions already recorded with the same task and start are skipped, so
+    // importing the same file twice is harmless.
+    pub fn import(&self, sessions: &[ImportedSession]) -> Result<ImportReport, String> {
+        let mut report = ImportReport {
+            imported: 0,
+            skipped: 0,
+            duration: 0,
+        };
+        match self.auth_manager.get_access_token() {
+            Some(token) => {
+                let endpoint = format!("{}/punch/import", self.configs.api_endpoint);
+                for batch in sessions.chunks(UPLOAD_BATCH_SIZE) {
+                    let payload = batch
+                        .iter()
+                        .map(|session| APIImportedTask {
+                            name: session.name.to_lowercase(),
+                            started_at: session.started_at,
+                            finished_at: Some(session.finished_at),
+                            tags: normalize_tags(&session.tags),
+                            note: session.note.to_owned(),
+                            pomodoro: false,
+                        })
+                        .collect();
+                    match import_tasks(&endpoint, &token, payload) {
+                        Ok(imported) => {
+                            report.imported += imported.imported;
+                            report.skipped += imported.skipped;
+                            report.duration += imported.duration;
+                        }
+                        Err(ApiError::Network(_)) => {
+                            self.offline.set(true);
+                            return Err(String::from("importing sessions requires the server"));
+                        }
+                        Err(err) => return Err(self.rejected(err)),
+                    }
+                }
+                // refreshes the cached totals shown while offline
+                self.list(&[])?;
+            }
+            None => {
+                let local_op = self.db_conn.transaction::<_, diesel::result::Error, _>(|| {
+                    for session in sessions {
+                        if session_exists(&session.name, session.started_at, self.db_conn)? {
+                            report.skipped += 1;
+                            continue;
+                        }
+                        let new_task = NewTask {
+                            name: session.name.to_owned(),
+                            started_at: session.started_at,
+                            finished_at: Some(session.finished_at),
+                            uploaded: false,
+                            note: session.note.to_owned(),
+                        };
+                        self.insert_task(&new_task, &normalize_tags(&session.tags))?;
+                        report.imported += 1;
+                        report.duration += session.finished_at - session.started_at;
+                    }
+                    Ok(())
+                });
+                local_op.map_err(|err| err.to_string())?;
+            }
+        }
+        Ok(report)
+    }
+
+    // a refused token is looked at once more before it is given up on
+    fn rejected(&self, err: ApiError) -> String {
+        match err {
+            ApiError::Auth(message) => self.auth_manager.reject_token(message),
+            err => err.to_string(),
+        }
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline.get()
+    }
+
+    fn is_online(&self) -> bool {
+        // punches have to reach the server in the order they were made, so
+        // anything new goes to the back of the queue until it is drained.
+        !self.offline.get()
+            && matches!(get_pending_operations(self.db_conn), Ok(ops) if ops.is_empty())
+    }
+
+    fn queue(
+        &self,
+        kind: &str,
+        task_name: &str,
+        timestamp: i64,
+        tags: &[String],
+        note: Option<&str>,
+        pomodoro: bool,
+    ) -> Result<(), String> {
+        let operation = NewPendingOperation {
+            kind: kind.to_owned(),
+            task_name: task_name.to_owned(),
+            timestamp,
+            tags: tags.join(","),
+            note: note.map(|note| note.to_owned()),
+            finished_at: None,
+            force: false,
+            pomodoro,
+        };
+        if let Err(err) = queue_operation(&operation, self.db_conn) {
+            return Err(format!("{}", err));
+        }
+        self.record_locally(kind, task_name, timestamp, tags, note, pomodoro)
+    }
+
+    fn record_locally(
+        &self,
+        kind: &str,
+        task_name: &str,
+        timestamp: i64,
+        tags: &[String],
+        note: Option<&str>,
+        pomodoro: bool,
+    ) -> Result<(), String> {
+        let unfinished = get_unfinished_task(task_name, self.db_conn);
+        let local_op = match (kind, unfinished.first()) {
+            (PUNCH_IN, None) => self
+                .insert_task(
+                    &NewTask {
+                        name: task_name.to_owned(),
+                        started_at: timestamp,
+                        finished_at: None,
+                        uploaded: true,
+                        note: None,
+                    },
+                    tags,
+                )
+                .map(|_| 1),
+            (PUNCH_OUT, Some(task)) => diesel::update(table.find(task.id))
+                .set((
+                    finished_at.eq(timestamp),
+                    tasks::note.eq(note.map(sealed)),
+                    tasks::pomodoro.eq(pomodoro),
+                ))
+                .execute(self.db_conn),
+            (PUNCH_CANCEL, Some(task)) => diesel::delete(table.find(task.id)).execute(self.db_conn),
+            _ => Ok(0),
+        };
+        local_op
+            .and_then(|_| apply_to_cache(kind, task_name, timestamp, pomodoro, self.db_conn))
+            .map(|_| ())
+            .map_err(|err| format!("{}", err))
+    }
+
+    fn cached_task_policy(&self) -> TaskPolicy {
+        get_setting(TASK_POLICY, self.db_conn)
+            .and_then(|policy| TaskPolicy::parse(&policy).ok())
+            .unwrap_or(TaskPolicy::Concurrent)
+    }
+
+    // checks a punch in that cannot reach the server against the policy,
+    // returns when the task started if the policy already took care of it.
+    fn apply_task_policy(&self, task_name: &str, tags: &[String]) -> Result<Option<i64>, String> {
+        match self.cached_task_policy() {
+            TaskPolicy::Concurrent => Ok(None),
+            TaskPolicy::Single => {
+                if !self.known_task_names(true)?.is_empty() {
+                    return Err(String::from("another task is already in progress"));
+                }
+                Ok(None)
+            }
+            TaskPolicy::AutoSwitch => self
+                .switch(task_name.to_owned(), tags)
+                .map(|report| Some(report.started_at)),
+        }
+    }
+
+    fn record_switch_locally(
+        &self,
+        task_name: &str,
+        timestamp: i64,
+        tags: &[String],
+    ) -> Result<(), String> {
+        self.db_conn
+            .transaction(|| self.switch_locally(task_name, timestamp, tags, true))
+            .map(|_| ())
+            .map_err(|err: diesel::result::Error| err.to_string())
+    }
+
+    // the cached copy of the server data is only kept while logged in, which
+    // is also when the sessions count as uploaded.
+    fn switch_locally(
+        &self,
+        task_name: &str,
+        timestamp: i64,
+        tags: &[String],
+        logged_in: bool,
+    ) -> QueryResult<Vec<Task>> {
+        let mut stopped = finish_unfinished_tasks(timestamp, self.db_conn)?;
+        if logged_in {
+            for cached in get_cached_tasks(self.db_conn)? {
+                if cached.finished_at.is_some() {
+                    continue;
+                }
+                apply_to_cache(PUNCH_OUT, &cached.name, timestamp, false, self.db_conn)?;
+                // running tasks only known to the cache were started elsewhere
+                if !stopped.iter().any(|task| task.name == cached.name) {
+                    stopped.push(Task {
+                        id: 0,
+                        name: cached.name,
+                        started_at: cached.started_at,
+                        finished_at: Some(timestamp),
+                        uploaded: true,
+                        note: None,
+                        pomodoro: false,
+                    });
+                }
+            }
+            apply_to_cache(PUNCH_IN, task_name, timestamp, false, self.db_conn)?;
+        }
+        self.insert_task(
+            &NewTask {
+                name: task_name.to_owned(),
+                started_at: timestamp,
+                finished_at: None,
+                uploaded: logged_in,
+                note: None,
+            },
+            tags,
+        )?;
+        Ok(stopped)
+    }
+
+    fn record_added_locally(
+        &self,
+        task_name: &str,
+        from_ts: i64,
+        to_ts: i64,
+        tags: &[String],
+        note: &Option<String>,
+    ) -> Result<i32, String> {
+        let new_task = NewTask {
+            name: task_name.to_owned(),
+            started_at: from_ts,
+            finished_at: Some(to_ts),
+            uploaded: true,
+            note: note.to_owned(),
+        };
+        self.insert_task(&new_task, tags)
+            .and_then(|task_id| {
+                add_to_cache(task_name, from_ts, to_ts, self.db_conn).map(|_| task_id)
+            })
+            .map_err(|err| format!("{}", err))
+    }
+
+    // the local mirror does not know the server ids, the copy of the session
+    // is the one that started when the session did before the edit. sessions
+    // punched on another machine or compacted have no copy to update.
+    fn record_edit_locally(
+        &self,
+        task_name: &str,
+        before: (i64, Option<i64>),
+        after: (i64, Option<i64>),
+    ) -> Result<(), String> {
+        self.db_conn
+            .transaction(|| {
+                diesel::update(
+                    table
+                        .filter(name.eq(sealed(task_name)))
+                        .filter(started_at.eq(before.0)),
+                )
+                .set((started_at.eq(after.0), finished_at.eq(after.1)))
+                .execute(self.db_conn)?;
+                edit_in_cache(task_name, before, after, self.db_conn)
+            })
+            .map(|_| ())
+            .map_err(|err: diesel::result::Error| err.to_string())
+    }
+
+    fn insert_task(&self, new_task: &NewTask, tags: &[String]) -> QueryResult<i32> {
+        self.db_conn.transaction(|| {
+            new_task.insert(self.db_conn)?;
+            let inserted = table
+                .order(tasks::id.desc())
+                .select(tasks::id)
+                .first::<i32>(self.db_conn)?;
+            add_task_tags(inserted, tags, self.db_conn)?;
+            Ok(inserted)
+        })
+    }
+
+    fn check_overlaps(
+        &self,
+        task_name: &str,
+        from_ts: i64,
+        to_ts: i64,
+        except_id: Option<i32>,
+    ) -> Result<(), String> {
+        let overlapping = get_overlapping_tasks(task_name, from_ts, to_ts, self.db_conn)
+            .map_err(|err| err.to_string())?;
+        if overlapping.iter().any(|task| Some(task.id) != except_id) {
+            return Err(String::from(
+                "the session overlaps another session of the same task",
+            ));
+        }
+        Ok(())
+    }
+
+    // the server hands out sessions a page at a time
+    fn fetch_sessions<F>(
+        &self,
+        token: &str,
+        task_name: &Option<String>,
+        filter: &SessionFilter,
+        on_page: &mut F,
+    ) -> Result<(), ApiError>
+    where
+        F: FnMut(Vec<TaskSession>) -> Result<(), String>,
+    {
+        let mut filters = vec![];
+        if let Some(task_name) = task_name {
+            filters.push(format!("name={}", encode(task_name)));
+        }
+        if let Some(since) = filter.since {
+            filters.push(format!("since={}", since));
+        }
+        if let Some(until) = filter.until {
+            filters.push(format!("until={}", until));
+        }
+        if filter.oldest_first {
+            filters.push(String::from("order=asc"));
+        }
+        let mut fetched = 0;
+        loop {
+            let page_size = match filter.limit {
+                Some(limit) => cmp::min(limit - fetched, SESSION_PAGE_SIZE),
+                None => SESSION_PAGE_SIZE,
+            };
+            if page_size == 0 {
+                break;
+            }
+            let endpoint = format!(
+                "{}/punch/sessions?{}limit={}&offset={}",
+                self.configs.api_endpoint,
+                filters
+                    .iter()
+                    .map(|filter| format!("{}&", filter))
+                    .collect::<String>(),
+                page_size,
+                fetched,
+            );
+            let page = match list_sessions(&endpoint, token) {
+                Ok(page) => page,
+                // falling back to the local copy would repeat what was handed over
+                Err(ApiError::Network(err)) if fetched > 0 => {
+                    return Err(ApiError::Rejected(format!(
+                        "lost the connection to the server: {}",
+                        err
+                    )))
+                }
+                Err(err) => return Err(err),
+            };
+            let is_last_page = page.len() < page_size;
+            fetched += page.len();
+            on_page(
+                page.into_iter()
+                    .map(|session| TaskSession {
+                        id: session.id,
+                        name: session.name,
+                        started_at: session.started_at,
+                        finished_at: session.finished_at,
+                        note: session.note,
+                        tags: session.tags,
+                    })
+                    .collect(),
+            )
+            .map_err(ApiError::Rejected)?;
+            if is_last_page {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_local_report(
+        &self,
+        period: ReportPeriod,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<ReportRow>, String> {
+        let now_ts = get_ts().map_err(|err| err.to_string())?.as_secs() as i64;
+        let sessions: Vec<(String, i64, i64)> =
+            get_task_sessions(None, Some(since), Some(until), None, false, self.db_conn)
+                .map_err(|err| err.to_string())?
+                .into_iter()
+                .map(|task| {
+                    (
+                        task.name,
+                        task.started_at,
+                        task.finished_at.unwrap_or(now_ts),
+                    )
+                })
+                .chain(
+                    get_task_aggregates(None, since, until, self.db_conn)
+                        .map_err(|err| err.to_string())?
+                        .iter()
+                        .map(|aggregate| aggregate.as_session()),
+                )
+                .collect();
+        Ok(split_into_periods(
+            &sessions,
+            period,
+            since,
+            cmp::min(until, now_ts),
+        ))
+    }
+
+    fn get_local_sessions(
+        &self,
+        task_name: Option<&str>,
+        filter: &SessionFilter,
+    ) -> Result<Vec<TaskSession>, String> {
+        let sessions = get_task_sessions(
+            task_name,
+            filter.since,
+            filter.until,
+            filter.limit,
+            filter.oldest_first,
+            self.db_conn,
+        )
+        .map_err(|err| err.to_string())?;
+        Ok(sessions
+            .into_iter()
+            .map(|session| TaskSession {
+                id: session.id as i64,
+                tags: get_task_tags(session.id, self.db_conn).unwrap_or_default(),
+                name: session.name,
+                started_at: session.started_at,
+                finished_at: session.finished_at,
+                note: session.note,
+            })
+            .collect())
+    }
+
+    fn get_cached(
+        &self,
+        task_name: Option<String>,
+        since: i64,
+        until: i64,
+        tags: &[String],
+    ) -> Result<TaskStat, String> {
+        let task_name = match task_name {
+            Some(task_name) if tags.is_empty() => task_name.to_lowercase(),
+            _ => return Err(String::from("filtering by tag requires the server")),
+        };
+        let cached_tasks: Vec<CachedTask> = get_cached_tasks(self.db_conn)
+            .map_err(|err| err.to_string())?
+            .into_iter()
+            .filter(|task| match task_prefix(&task_name) {
+                Some(prefix) => is_under_prefix(&task.name, prefix),
+                None => task.name == task_name,
+            })
+            .collect();
+        if cached_tasks.is_empty() {
+            return Err(format!("no task found for {}", task_name));
+        }
+        // the cache only holds the totals, which are those of the period
+        // unless it leaves out some of the time
+        let last_recorded = cached_tasks
+            .iter()
+            .map(|task| task.finished_at.unwrap_or(task.started_at))
+            .max()
+            .unwrap_or(0);
+        if since > 0 || until < last_recorded {
+            return Err(String::from("filtering by time requires the server"));
+        }
+        let now_ts = get_ts().map_err(|err| err.to_string())?.as_secs() as i64;
+        let mut in_progress = false;
+        let mut duration = 0;
+        let mut pomodoros = 0;
+        for task in cached_tasks.iter() {
+            duration += task.duration;
+            pomodoros += task.pomodoros;
+            if task.finished_at.is_none() {
+                in_progress = true;
+                duration += cmp::max(
+                    cmp::min(now_ts, until) - cmp::max(task.started_at, since),
+                    0,
+                );
+            }
+        }
+        Ok(TaskStat {
+            name: task_name,
+            status: if in_progress {
+                "in progress"
+            } else {
+                "complete"
+            }
+            .to_owned(),
+            duration,
+            pomodoros,
+        })
+    }
+}
+
+fn validate_session_range(from_ts: i64, to_ts: Option<i64>, now_ts: i64) -> Result<(), String> {
+    if matches!(to_ts, Some(to_ts) if to_ts <= from_ts) {
+        return Err(String::from("the session must finish after it starts"));
+    }
+    if to_ts.unwrap_or(from_ts) > now_ts {
+        return Err(String::from("the session cannot finish in the future"));
+    }
+    Ok(())
+}
+
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| tag.to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::aggregate::compact_sessions;
+    use crate::database::database::test_connection;
+
+    struct LoggedOut;
+
+    impl SecretsManager for LoggedOut {
+        fn remove_secret(&self) {}
+
+        fn retrieve_secrets(&self) -> keyring::Result<String> {
+            Err(keyring::Error::NoEntry)
+        }
+
+        fn save_secrets(&self, _: &str) {}
+    }
+
+    fn test_configs() -> AppConfigs {
+        AppConfigs {
+            profile: String::from("default"),
+            api_endpoint: String::new(),
+            gh_auth_scope: String::new(),
+            output: None,
+            timezone: None,
+            proxy: None,
+            ca_bundle: None,
+            timeout: None,
+        }
+    }
+
+    fn add_session(
+        puncher: &Puncher<LoggedOut>,
+        task_name: &str,
+        from_ts: i64,
+        to_ts: i64,
+        uploaded: bool,
+    ) {
+        let new_task = NewTask {
+            name: task_name.to_owned(),
+            started_at: from_ts,
+            finished_at: Some(to_ts),
+            uploaded,
+            note: None,
+        };
+        puncher
+            .insert_task(&new_task, &[String::from("work")])
+            .unwrap();
+    }
+
+    type Totals = (
+        Vec<(String, i64)>,
+        Vec<(String, i64, i64)>,
+        Vec<(i64, String, i64)>,
+    );
+
+    fn totals(puncher: &Puncher<LoggedOut>, since: i64, until: i64) -> Totals {
+        let gets = ["alpha", "beta", "project/"]
+            .iter()
+            .map(|task_name| {
+                let stat = puncher
+                    .get(Some(task_name.to_string()), since, until, &[])
+                    .unwrap();
+                (stat.name, stat.duration)
+            })
+            .collect();
+        let list = puncher
+            .list(&[])
+            .unwrap()
+            .into_iter()
+            .map(|item| (item.name, item.duration, item.pomodoros))
+            .collect();
+        let report = puncher
+            .report(ReportPeriod::Day, since, until)
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.period_start, row.name, row.duration))
+            .collect();
+        (gets, list, report)
+    }
+
+    #[test]
+    fn compacting_keeps_the_totals() {
+        let conn = test_connection();
+        let configs = test_configs();
+        let auth_manager = AuthManager::new(&configs, &LoggedOut);
+        let puncher = Puncher::new(&auth_manager, &configs, &conn);
+        let first_day = ReportPeriod::Day.start_of(1_600_000_000);
+        let midnight = ReportPeriod::Day.next_start(first_day);
+        let last_day = ReportPeriod::Day.next_start(midnight);
+        add_session(&puncher, "alpha", first_day + 3600, first_day + 7200, true);
+        add_session(&puncher, "alpha", midnight - 3600, midnight + 1800, false);
+        add_session(&puncher, "alpha", midnight + 3600, midnight + 4200, true);
+        add_session(&puncher, "beta", midnight + 100, midnight + 400, false);
+        add_session(
+            &puncher,
+            "project/api",
+            first_day + 600,
+            first_day + 900,
+            true,
+        );
+        add_session(&puncher, "project/web", midnight - 60, midnight + 60, true);
+
+        let before = totals(&puncher, first_day, last_day);
+        let to_upload = puncher.count_tasks_to_upload().unwrap();
+        let report = compact_sessions(last_day, &conn).unwrap();
+        assert_eq!(report.sessions, 6);
+        assert_eq!(puncher.count_tasks_to_upload().unwrap(), to_upload);
+        assert_eq!(before, totals(&puncher, first_day, last_day));
+        assert_eq!(before.0[0], (String::from("alpha"), 9600));
+    }
 }
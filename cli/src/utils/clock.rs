@@ -17,3 +17,55 @@ pub fn seconds_to_duration(seconds: i64) -> String {
 pub fn get_now_ts() -> Result<Duration, SystemTimeError> {
     SystemTime::now().duration_since(UNIX_EPOCH)
 }
+
+// durations like 25m, 1h30m or 90s, a bare number is taken as minutes
+pub fn parse_duration(value: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid duration '{}', use e.g. 25m or 1h30m", value);
+    let value = value.trim();
+    if let Ok(minutes) = value.parse::<i64>() {
+        return Ok(minutes * 60);
+    }
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' | 's' => {
+                let amount: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                seconds += amount
+                    * match c {
+                        'h' => 3600,
+                        'm' => 60,
+                        _ => 1,
+                    };
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    if !number.is_empty() || seconds <= 0 {
+        return Err(invalid());
+    }
+    Ok(seconds)
+}
+
+// ages like 90d, 6w, 3mo or 1y, a month is 30 days and a year 365
+pub fn parse_age(value: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid age '{}', use e.g. 90d, 6mo or 1y", value);
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let amount: i64 = value[..split].parse().map_err(|_| invalid())?;
+    let days = match &value[split..] {
+        "d" => 1,
+        "w" => 7,
+        "mo" => 30,
+        "y" => 365,
+        _ => return Err(invalid()),
+    };
+    if amount <= 0 {
+        return Err(invalid());
+    }
+    Ok(amount * days * 86400)
+}
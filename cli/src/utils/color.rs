@@ -0,0 +1,38 @@
+#![allow(non_upper_case_globals)]
+
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ansi_term::{ANSIGenericString, Colour, Style};
+
+static COLORS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+// stands in for ansi_term's colours so they can be turned off for the whole
+// run, e.g. with --no-color or when the output is meant for other programs.
+#[derive(Clone, Copy)]
+pub struct Paint(Colour);
+
+pub const Cyan: Paint = Paint(Colour::Cyan);
+pub const Green: Paint = Paint(Colour::Green);
+pub const Purple: Paint = Paint(Colour::Purple);
+pub const Red: Paint = Paint(Colour::Red);
+pub const Yellow: Paint = Paint(Colour::Yellow);
+
+impl Paint {
+    pub fn paint<'a, I, S: 'a + ToOwned + ?Sized>(self, input: I) -> ANSIGenericString<'a, S>
+    where
+        I: Into<Cow<'a, S>>,
+        <S as ToOwned>::Owned: fmt::Debug,
+    {
+        if COLORS_ENABLED.load(Ordering::Relaxed) {
+            self.0.paint(input)
+        } else {
+            Style::new().paint(input)
+        }
+    }
+}
+
+pub fn set_colors_enabled(enabled: bool) {
+    COLORS_ENABLED.store(enabled, Ordering::Relaxed);
+}
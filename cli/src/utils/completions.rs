@@ -0,0 +1,150 @@
+use std::io::Write;
+
+use clap::Command;
+use clap_complete::{generate, Shell};
+
+pub const BIN_NAME: &str = "punch";
+
+// a typo in a task name silently starts a new task, so these suggest the
+// names punch already knows about instead of files.
+const IN_PROGRESS_TASK_COMMANDS: [&str; 3] = ["out", "cancel", "status"];
+const TASK_COMMANDS: [&str; 6] = ["in", "switch", "add", "get", "log", "timer"];
+
+// clap only knows the static parts of the command line, the task names are
+// looked up by calling `punch __complete-tasks` from the generated script.
+pub fn write_completions<W: Write>(shell: Shell, cmd: &mut Command, out: &mut W) {
+    let mut script = vec![];
+    generate(shell, cmd, BIN_NAME, &mut script);
+    let script = String::from_utf8(script).unwrap();
+    // the options given before the subcommand whose value is the next word
+    let value_options: Vec<String> = cmd
+        .get_arguments()
+        .filter(|arg| arg.is_takes_value_set())
+        .filter_map(|arg| arg.get_long())
+        .map(|long| format!("--{}", long))
+        .collect();
+    let script = match shell {
+        Shell::Bash => format!(
+            "{}\n{}",
+            script,
+            bash_task_completion(&value_options.join("|"))
+        ),
+        Shell::Zsh => zsh_task_completion(&script),
+        Shell::Fish => format!("{}{}", script, fish_task_completion()),
+        _ => script,
+    };
+    out.write_all(script.as_bytes()).unwrap();
+}
+
+// the options before the subcommand are skipped to find it, and passed on so
+// that the tasks come from the same profile and database.
+fn bash_task_completion(value_options: &str) -> String {
+    format!(
+        r#"_{bin}_with_tasks() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local i=1
+    local options=()
+    while [[ ${{i}} -lt ${{COMP_CWORD}} ]] ; do
+        case "${{COMP_WORDS[i]}}" in
+            {value_options})
+                # bash splits --option=value into three words
+                if [[ ${{COMP_WORDS[i+1]}} == "=" ]] ; then
+                    options+=( "${{COMP_WORDS[i]}}=${{COMP_WORDS[i+2]}}" )
+                    i=$((i + 3))
+                else
+                    options+=( "${{COMP_WORDS[i]}}" "${{COMP_WORDS[i+1]}}" )
+                    i=$((i + 2))
+                fi
+                ;;
+            -*)
+                options+=( "${{COMP_WORDS[i]}}" )
+                i=$((i + 1))
+                ;;
+            *)
+                break
+                ;;
+        esac
+    done
+    if [[ $((i + 1)) -eq ${{COMP_CWORD}} && ${{cur}} != -* ]] ; then
+        case "${{COMP_WORDS[i]}}" in
+            {in_progress})
+                local IFS=$'\n'
+                COMPREPLY=( $(compgen -W "$({bin} "${{options[@]}}" __complete-tasks --in-progress 2>/dev/null)" -- "${{cur}}") )
+                return 0
+                ;;
+            {all})
+                local IFS=$'\n'
+                COMPREPLY=( $(compgen -W "$({bin} "${{options[@]}}" __complete-tasks 2>/dev/null)" -- "${{cur}}") )
+                return 0
+                ;;
+        esac
+    fi
+    _{bin} "$@"
+}}
+
+complete -F _{bin}_with_tasks -o bashdefault -o default {bin}
+"#,
+        bin = BIN_NAME,
+        value_options = value_options,
+        in_progress = IN_PROGRESS_TASK_COMMANDS.join("|"),
+        all = TASK_COMMANDS.join("|"),
+    )
+}
+
+// zsh completes each argument with its own action, so the empty action of the
+// NAME argument is pointed at a function listing the tasks.
+fn zsh_task_completion(script: &str) -> String {
+    let mut lines = vec![];
+    let mut subcommand = "";
+    for line in script.lines() {
+        if let Some(name) = line
+            .strip_prefix('(')
+            .and_then(|line| line.strip_suffix(')'))
+        {
+            subcommand = name;
+        }
+        let action = if IN_PROGRESS_TASK_COMMANDS.contains(&subcommand) {
+            Some(format!("_{}_in_progress_tasks", BIN_NAME))
+        } else if TASK_COMMANDS.contains(&subcommand) {
+            Some(format!("_{}_tasks", BIN_NAME))
+        } else {
+            None
+        };
+        match action {
+            Some(action) if line == "'::NAME:' \\" || line == "':NAME:' \\" => {
+                lines.push(line.replace("NAME:'", &format!("NAME:{}'", action)));
+            }
+            _ if line == format!("_{} \"$@\"", BIN_NAME) => {
+                lines.push(format!(
+                    r#"(( $+functions[_{bin}_tasks] )) ||
+_{bin}_tasks() {{
+    local -a tasks
+    tasks=(${{(f)"$({bin} __complete-tasks 2>/dev/null)"}})
+    compadd -a tasks
+}}
+(( $+functions[_{bin}_in_progress_tasks] )) ||
+_{bin}_in_progress_tasks() {{
+    local -a tasks
+    tasks=(${{(f)"$({bin} __complete-tasks --in-progress 2>/dev/null)"}})
+    compadd -a tasks
+}}
+"#,
+                    bin = BIN_NAME
+                ));
+                lines.push(line.to_owned());
+            }
+            _ => lines.push(line.to_owned()),
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+fn fish_task_completion() -> String {
+    format!(
+        "complete -c {bin} -n \"__fish_seen_subcommand_from {in_progress}\" -f -a \"({bin} __complete-tasks --in-progress)\"\n\
+         complete -c {bin} -n \"__fish_seen_subcommand_from {all}\" -f -a \"({bin} __complete-tasks)\"\n",
+        bin = BIN_NAME,
+        in_progress = IN_PROGRESS_TASK_COMMANDS.join(" "),
+        all = TASK_COMMANDS.join(" "),
+    )
+}
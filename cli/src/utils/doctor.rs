@@ -0,0 +1,378 @@
+use std::path::Path;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use keyring::Error as KeyringError;
+use reqwest::header::DATE;
+use serde::Serialize;
+
+use crate::api::{
+    api::verify_access_token,
+    client::{configure_client, send},
+};
+use crate::database::{
+    database::{create_connection, default_database_path},
+    migrations::{pending_migrations, schema_version},
+    sealed::{is_encrypted, unlock_database},
+};
+use crate::managers::{
+    auth::{token_expiry, AuthManager, TokenState},
+    configs::{AppConfigs, ConfigFile, DEFAULT_PROFILE},
+    keyring::{new_database_key_manager, new_key_ring_manager, SecretsManager},
+};
+use crate::utils::clock::{seconds_to_duration, utc_ts_to_local_datetime};
+
+// punches and tokens carry timestamps, a minute either way is tolerated
+const MAX_CLOCK_SKEW: i64 = 60;
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+    // the check could not run, e.g. without a server to ask
+    Skip,
+}
+
+impl CheckStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "ok",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail => "fail",
+            CheckStatus::Skip => "skip",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass<D: Into<String>>(name: &'static str, detail: D) -> Check {
+        Check {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn skip<D: Into<String>>(name: &'static str, detail: D) -> Check {
+        Check {
+            name,
+            status: CheckStatus::Skip,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warn<D: Into<String>, H: Into<String>>(name: &'static str, detail: D, hint: H) -> Check {
+        Check {
+            name,
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail<D: Into<String>, H: Into<String>>(name: &'static str, detail: D, hint: H) -> Check {
+        Check {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+// every check runs on its own, so that one broken piece does not hide the
+// state of the others
+pub fn run_checks(
+    profile: Option<&str>,
+    configs: &Result<AppConfigs, String>,
+    db_override: Option<&Path>,
+) -> Vec<Check> {
+    let mut checks = vec![];
+    let config_path = ConfigFile::path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default();
+    let profile = match configs {
+        Ok(cf) => {
+            checks.push(Check::pass(
+                "configuration",
+                format!("profile {} from {}", cf.profile, config_path),
+            ));
+            cf.profile.to_owned()
+        }
+        Err(err) => {
+            checks.push(Check::fail(
+                "configuration",
+                err.to_owned(),
+                format!(
+                    "fix {} with punch config set, or pick another profile with --profile",
+                    config_path
+                ),
+            ));
+            ConfigFile::load()
+                .map(|file| file.active_profile(profile))
+                .unwrap_or_else(|_| String::from(DEFAULT_PROFILE))
+        }
+    };
+
+    let sm = new_key_ring_manager(&profile);
+    let token = match sm.retrieve_secrets() {
+        Ok(token) => {
+            checks.push(Check::pass("keyring", "a session is stored"));
+            Some(token)
+        }
+        Err(KeyringError::NoEntry) => {
+            checks.push(Check::pass("keyring", "available, no session stored"));
+            None
+        }
+        Err(err) => {
+            checks.push(Check::fail(
+                "keyring",
+                format!("the keyring is not available: {}", err),
+                "start a secret service such as gnome-keyring, punch works locally until then",
+            ));
+            None
+        }
+    };
+
+    checks.push(check_database(&profile, db_override));
+
+    let cf = match configs {
+        Ok(cf) => cf,
+        Err(_) => {
+            for name in ["server", "session", "clock"] {
+                checks.push(Check::skip(name, "the configuration could not be read"));
+            }
+            return checks;
+        }
+    };
+    if cf.api_endpoint.is_empty() {
+        checks.push(Check::warn(
+            "server",
+            "no server is configured, punch works locally",
+            "set one with punch config set endpoint <URL>",
+        ));
+        checks.push(Check::skip("session", "no server is configured"));
+        checks.push(Check::skip("clock", "no server is configured"));
+        return checks;
+    }
+    let (server_check, server_time) = check_server(cf);
+    let is_reachable = server_check.status == CheckStatus::Pass;
+    checks.push(server_check);
+    checks.push(check_session(cf, &sm, token.as_deref(), is_reachable));
+    checks.push(match server_time {
+        Some(server_time) => check_clock(server_time),
+        None if is_reachable => Check::warn(
+            "clock",
+            "the server did not send its time",
+            "compare the clock with another machine",
+        ),
+        None => Check::skip("clock", "the server could not be reached"),
+    });
+    checks
+}
+
+fn check_database(profile: &str, db_override: Option<&Path>) -> Check {
+    let path = match db_override {
+        Some(path) => path.to_owned(),
+        None => match default_database_path(profile) {
+            Ok(path) => path,
+            Err(err) => return Check::fail("database", err, "set XDG_DATA_HOME or pass --db PATH"),
+        },
+    };
+    if !path.exists() {
+        return Check::pass(
+            "database",
+            format!(
+                "{} does not exist yet, the first punch creates it",
+                path.display()
+            ),
+        );
+    }
+    let conn = match create_connection(&path) {
+        Ok(conn) => conn,
+        Err(err) => {
+            return Check::fail(
+                "database",
+                err,
+                "check the permissions of the file and its directory, or pass --db PATH",
+            )
+        }
+    };
+    if is_encrypted(&conn) {
+        if let Err(err) = unlock_database(&conn, &new_database_key_manager(profile)) {
+            return Check::fail(
+                "database",
+                format!("{}: {}", path.display(), err),
+                "put the key back in the keyring of this profile, or restore a backup made before punch db encrypt",
+            );
+        }
+    }
+    match (schema_version(&conn), pending_migrations(&conn)) {
+        (Ok(version), Ok(pending)) if pending.is_empty() => Check::pass(
+            "database",
+            format!(
+                "{} at schema version {}",
+                path.display(),
+                version.unwrap_or_default()
+            ),
+        ),
+        (Ok(version), Ok(pending)) => Check::warn(
+            "database",
+            format!(
+                "{} at schema version {}, {} migration(s) pending",
+                path.display(),
+                version.unwrap_or_else(|| String::from("none")),
+                pending.len()
+            ),
+            "the next command applies them",
+        ),
+        (Err(err), _) | (_, Err(err)) => Check::fail(
+            "database",
+            format!("{} cannot be read: {}", path.display(), err),
+            format!(
+                "restore a backup of {} or move it away to start with an empty history",
+                path.display()
+            ),
+        ),
+    }
+}
+
+fn check_server(cf: &AppConfigs) -> (Check, Option<DateTime<Utc>>) {
+    if let Err(err) = configure_client(cf) {
+        return (
+            Check::fail(
+                "server",
+                err,
+                "fix the proxy or ca_bundle setting with punch config set",
+            ),
+            None,
+        );
+    }
+    let endpoint = format!("{}/ping", cf.api_endpoint);
+    let started = Instant::now();
+    match send(|client| client.get(&endpoint), false) {
+        Ok(resp) if resp.status().is_success() => {
+            let server_time = resp
+                .headers()
+                .get(DATE)
+                .and_then(|date| date.to_str().ok())
+                .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+                .map(|date| date.with_timezone(&Utc));
+            (
+                Check::pass(
+                    "server",
+                    format!(
+                        "{} answered in {} ms",
+                        cf.api_endpoint,
+                        started.elapsed().as_millis()
+                    ),
+                ),
+                server_time,
+            )
+        }
+        Ok(resp) => (
+            Check::fail(
+                "server",
+                format!("{} answered {}", endpoint, resp.status()),
+                "check that the endpoint points to a punch server",
+            ),
+            None,
+        ),
+        Err(err) => (
+            Check::fail(
+                "server",
+                err.to_string(),
+                "check the endpoint, the proxy and the network, punches are queued until the server is back",
+            ),
+            None,
+        ),
+    }
+}
+
+fn check_session<T: SecretsManager>(
+    cf: &AppConfigs,
+    sm: &T,
+    token: Option<&str>,
+    is_reachable: bool,
+) -> Check {
+    let token = match token {
+        Some(token) => token,
+        None => {
+            return Check::warn(
+                "session",
+                "not logged in, punches are kept locally",
+                "log in with punch login",
+            )
+        }
+    };
+    let valid_until = token_expiry(token)
+        .map(|expires_at| {
+            format!(
+                ", valid until {}",
+                utc_ts_to_local_datetime(expires_at as i64)
+            )
+        })
+        .unwrap_or_default();
+    match AuthManager::new(cf, sm).token_state() {
+        TokenState::Expired => Check::fail(
+            "session",
+            "the session has expired",
+            "log in again with punch login",
+        ),
+        TokenState::Invalid => Check::fail(
+            "session",
+            "the server refused the session",
+            "log in again with punch login",
+        ),
+        _ if is_reachable => {
+            let endpoint = format!("{}/auth/verify", cf.api_endpoint);
+            match verify_access_token(&endpoint, token) {
+                Ok(true) => Check::pass("session", format!("logged in{}", valid_until)),
+                Ok(false) => Check::fail(
+                    "session",
+                    "the server refused the session",
+                    "log in again with punch login",
+                ),
+                Err(err) => Check::warn(
+                    "session",
+                    format!("failed to verify the session: {}", err),
+                    "run punch doctor again once the server is reachable",
+                ),
+            }
+        }
+        _ => Check::warn(
+            "session",
+            format!("not verified with the server{}", valid_until),
+            "run punch doctor again once the server is reachable",
+        ),
+    }
+}
+
+fn check_clock(server_time: DateTime<Utc>) -> Check {
+    let skew = Utc::now().timestamp() - server_time.timestamp();
+    if skew.abs() <= MAX_CLOCK_SKEW {
+        return Check::pass("clock", format!("{} s off the server", skew.abs()));
+    }
+    Check::fail(
+        "clock",
+        format!(
+            "the clock is {} {} the server",
+            seconds_to_duration(skew.abs()),
+            if skew > 0 { "ahead of" } else { "behind" }
+        ),
+        "sync it with the network time, e.g. timedatectl set-ntp true",
+    )
+}
@@ -0,0 +1,149 @@
+use std::io::Write;
+
+use chrono::{Local, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+
+use crate::puncher::TaskSession;
+
+// exports are meant to be diffed, so the columns never change order
+const CSV_COLUMNS: [&str; 7] = [
+    "id",
+    "name",
+    "started_at",
+    "finished_at",
+    "duration",
+    "tags",
+    "note",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Result<ExportFormat, String> {
+        match format {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "ndjson" => Ok(ExportFormat::Ndjson),
+            _ => Err(format!("unknown export format '{}'", format)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExportedSession<'a> {
+    id: i64,
+    name: &'a str,
+    started_at: String,
+    finished_at: Option<String>,
+    duration: i64,
+    tags: &'a [String],
+    note: Option<&'a str>,
+}
+
+pub struct SessionExporter<W: Write> {
+    format: ExportFormat,
+    time_zone: Option<Tz>,
+    now_ts: i64,
+    out: W,
+    written: usize,
+}
+
+impl<W: Write> SessionExporter<W> {
+    // without a time zone, timestamps are written in the local one
+    pub fn new(format: ExportFormat, time_zone: Option<Tz>, now_ts: i64, out: W) -> Self {
+        SessionExporter {
+            format,
+            time_zone,
+            now_ts,
+            out,
+            written: 0,
+        }
+    }
+
+    pub fn begin(&mut self) -> Result<(), String> {
+        match self.format {
+            ExportFormat::Csv => {
+                let mut csv_writer = csv::Writer::from_writer(&mut self.out);
+                csv_writer
+                    .write_record(CSV_COLUMNS)
+                    .and_then(|_| csv_writer.flush().map_err(csv::Error::from))
+                    .map_err(|err| err.to_string())
+            }
+            ExportFormat::Json => write!(self.out, "[").map_err(|err| err.to_string()),
+            ExportFormat::Ndjson => Ok(()),
+        }
+    }
+
+    pub fn write_sessions(&mut self, sessions: &[TaskSession]) -> Result<(), String> {
+        for session in sessions {
+            let exported = ExportedSession {
+                id: session.id,
+                name: &session.name,
+                started_at: self.format_ts(session.started_at),
+                finished_at: session.finished_at.map(|ts| self.format_ts(ts)),
+                duration: session.finished_at.unwrap_or(self.now_ts) - session.started_at,
+                tags: &session.tags,
+                note: session.note.as_deref(),
+            };
+            match self.format {
+                ExportFormat::Csv => self.write_csv_row(&exported)?,
+                ExportFormat::Json => {
+                    let separator = if self.written > 0 { "," } else { "" };
+                    let row = serde_json::to_string(&exported).map_err(|err| err.to_string())?;
+                    write!(self.out, "{}\n  {}", separator, row).map_err(|err| err.to_string())?;
+                }
+                ExportFormat::Ndjson => {
+                    let row = serde_json::to_string(&exported).map_err(|err| err.to_string())?;
+                    writeln!(self.out, "{}", row).map_err(|err| err.to_string())?;
+                }
+            };
+            self.written += 1;
+        }
+        self.out.flush().map_err(|err| err.to_string())
+    }
+
+    pub fn finish(&mut self) -> Result<usize, String> {
+        if self.format == ExportFormat::Json {
+            let closing = if self.written > 0 { "\n]" } else { "]" };
+            writeln!(self.out, "{}", closing).map_err(|err| err.to_string())?;
+        }
+        self.out.flush().map_err(|err| err.to_string())?;
+        Ok(self.written)
+    }
+
+    fn write_csv_row(&mut self, exported: &ExportedSession) -> Result<(), String> {
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(&mut self.out);
+        csv_writer
+            .write_record([
+                exported.id.to_string(),
+                exported.name.to_owned(),
+                exported.started_at.to_owned(),
+                exported.finished_at.to_owned().unwrap_or_default(),
+                exported.duration.to_string(),
+                exported.tags.join(","),
+                exported.note.unwrap_or_default().to_owned(),
+            ])
+            .and_then(|_| csv_writer.flush().map_err(csv::Error::from))
+            .map_err(|err| err.to_string())
+    }
+
+    fn format_ts(&self, ts: i64) -> String {
+        let utc_dt = Utc.timestamp_opt(ts, 0).unwrap();
+        match &self.time_zone {
+            Some(tz) => utc_dt
+                .with_timezone(tz)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            None => utc_dt
+                .with_timezone(&Local)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use crate::utils::tree::TASK_PATH_SEPARATOR;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportSource {
+    TogglCsv,
+    Timewarrior,
+    Ics,
+}
+
+pub struct ImportedSession {
+    pub name: String,
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+}
+
+pub struct ParsedImport {
+    pub sessions: Vec<ImportedSession>,
+    // entries that cannot become a session, such as running timers or
+    // all-day calendar events
+    pub ignored: usize,
+}
+
+#[derive(Deserialize)]
+struct TimewarriorInterval {
+    start: String,
+    end: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    annotation: Option<String>,
+}
+
+impl ImportSource {
+    pub fn parse(source: &str) -> Result<ImportSource, String> {
+        match source {
+            "toggl-csv" => Ok(ImportSource::TogglCsv),
+            "timewarrior" => Ok(ImportSource::Timewarrior),
+            "ics" => Ok(ImportSource::Ics),
+            _ => Err(format!("unknown import source '{}'", source)),
+        }
+    }
+}
+
+// `task_name` replaces whatever name the entries would otherwise map to,
+// which is mostly useful for calendars.
+pub fn parse_import(
+    source: ImportSource,
+    content: &str,
+    task_name: Option<&str>,
+) -> Result<ParsedImport, String> {
+    let mut parsed = match source {
+        ImportSource::TogglCsv => parse_toggl_csv(content)?,
+        ImportSource::Timewarrior => parse_timewarrior(content)?,
+        ImportSource::Ics => parse_ics(content)?,
+    };
+    if let Some(task_name) = task_name {
+        for session in parsed.sessions.iter_mut() {
+            session.name = task_name.to_owned();
+        }
+    }
+    let before = parsed.sessions.len();
+    parsed.sessions.retain(|session| {
+        session.finished_at > session.started_at && !session.name.trim().is_empty()
+    });
+    parsed.ignored += before - parsed.sessions.len();
+    // the same entry listed twice in a file is only imported once
+    parsed
+        .sessions
+        .sort_by(|a, b| (a.started_at, &a.name).cmp(&(b.started_at, &b.name)));
+    parsed
+        .sessions
+        .dedup_by(|a, b| a.started_at == b.started_at && a.name == b.name);
+    Ok(parsed)
+}
+
+// toggl's detailed report: the project becomes the task, its toggl task a
+// nested task under it, and the client one more tag.
+fn parse_toggl_csv(content: &str) -> Result<ParsedImport, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let headers: HashMap<String, usize> = reader
+        .headers()
+        .map_err(|err| err.to_string())?
+        .iter()
+        .enumerate()
+        .map(|(idx, header)| (header.trim().to_lowercase(), idx))
+        .collect();
+    for required in ["start date", "start time", "end date", "end time"] {
+        if !headers.contains_key(required) {
+            return Err(format!("the toggl export has no '{}' column", required));
+        }
+    }
+    let mut parsed = ParsedImport {
+        sessions: vec![],
+        ignored: 0,
+    };
+    for record in reader.records() {
+        let record = record.map_err(|err| err.to_string())?;
+        let field = |column: &str| -> String {
+            headers
+                .get(column)
+                .and_then(|idx| record.get(*idx))
+                .unwrap_or_default()
+                .trim()
+                .to_owned()
+        };
+        let started_at = parse_local_datetime(&field("start date"), &field("start time"));
+        let finished_at = parse_local_datetime(&field("end date"), &field("end time"));
+        let (started_at, finished_at) = match (started_at, finished_at) {
+            (Some(started_at), Some(finished_at)) => (started_at, finished_at),
+            _ => {
+                parsed.ignored += 1;
+                continue;
+            }
+        };
+        let description = field("description");
+        let name = match (field("project"), field("task")) {
+            (project, task) if !project.is_empty() && !task.is_empty() => {
+                format!("{}{}{}", project, TASK_PATH_SEPARATOR, task)
+            }
+            (project, _) if !project.is_empty() => project,
+            _ if !description.is_empty() => description.to_owned(),
+            _ => String::from("toggl"),
+        };
+        let mut tags: Vec<String> = field("tags")
+            .split(',')
+            .map(|tag| tag.trim().to_owned())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        let client = field("client");
+        if !client.is_empty() {
+            tags.push(client);
+        }
+        parsed.sessions.push(ImportedSession {
+            name,
+            started_at,
+            finished_at,
+            tags,
+            note: Some(description).filter(|description| !description.is_empty()),
+        });
+    }
+    Ok(parsed)
+}
+
+// accepts both `timew export` output and the raw `data/*.data` files, the
+// first tag of an interval becomes the task and the rest stay tags.
+fn parse_timewarrior(content: &str) -> Result<ParsedImport, String> {
+    let intervals: Vec<TimewarriorInterval> = if content.trim_start().starts_with('[') {
+        serde_json::from_str(content).map_err(|err| err.to_string())?
+    } else {
+        content
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| line.starts_with("inc "))
+            .map(parse_timewarrior_line)
+            .collect()
+    };
+    let mut parsed = ParsedImport {
+        sessions: vec![],
+        ignored: 0,
+    };
+    for interval in intervals {
+        let started_at = parse_utc_timestamp(&interval.start);
+        let finished_at = interval.end.as_deref().and_then(parse_utc_timestamp);
+        let (started_at, finished_at) = match (started_at, finished_at) {
+            (Some(started_at), Some(finished_at)) => (started_at, finished_at),
+            _ => {
+                parsed.ignored += 1;
+                continue;
+            }
+        };
+        let mut tags = interval.tags.into_iter();
+        let name = tags.next().unwrap_or_else(|| String::from("timewarrior"));
+        parsed.sessions.push(ImportedSession {
+            name,
+            started_at,
+            finished_at,
+            tags: tags.collect(),
+            note: interval.annotation.filter(|note| !note.is_empty()),
+        });
+    }
+    Ok(parsed)
+}
+
+fn parse_timewarrior_line(line: &str) -> TimewarriorInterval {
+    let mut sections = line.splitn(3, " # ");
+    let mut range = sections
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches("inc")
+        .split(" - ")
+        .map(|ts| ts.trim().to_owned());
+    let start = range.next().unwrap_or_default();
+    let end = range.next();
+    let tags = split_quoted(sections.next().unwrap_or_default());
+    let annotation = sections
+        .next()
+        .map(|annotation| annotation.trim().trim_matches('"').to_owned());
+    TimewarriorInterval {
+        start,
+        end,
+        tags,
+        annotation,
+    }
+}
+
+// calendar events keep their summary as the note, the first category names
+// the task and falls back to the summary.
+fn parse_ics(content: &str) -> Result<ParsedImport, String> {
+    if !content.contains("BEGIN:VCALENDAR") {
+        return Err(String::from("the file is not an iCalendar file"));
+    }
+    let mut parsed = ParsedImport {
+        sessions: vec![],
+        ignored: 0,
+    };
+    let mut event: Option<HashMap<String, (String, String)>> = None;
+    for line in unfold_ics_lines(content) {
+        if line == "BEGIN:VEVENT" {
+            event = Some(HashMap::new());
+            continue;
+        }
+        if line == "END:VEVENT" {
+            match event.take().and_then(|props| ics_event_to_session(&props)) {
+                Some(session) => parsed.sessions.push(session),
+                None => parsed.ignored += 1,
+            }
+            continue;
+        }
+        if let Some(props) = event.as_mut() {
+            if let Some((key, value)) = line.split_once(':') {
+                let (name, params) = match key.split_once(';') {
+                    Some((name, params)) => (name, params),
+                    None => (key, ""),
+                };
+                props
+                    .entry(name.to_uppercase())
+                    .or_insert((params.to_owned(), value.to_owned()));
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+fn ics_event_to_session(props: &HashMap<String, (String, String)>) -> Option<ImportedSession> {
+    let (start_params, start_value) = props.get("DTSTART")?;
+    let started_at = parse_ics_datetime(start_params, start_value)?;
+    let finished_at = match (props.get("DTEND"), props.get("DURATION")) {
+        (Some((end_params, end_value)), _) => parse_ics_datetime(end_params, end_value)?,
+        (None, Some((_, duration))) => started_at + parse_ics_duration(duration)?,
+        (None, None) => return None,
+    };
+    let summary = props
+        .get("SUMMARY")
+        .map(|(_, summary)| unescape_ics_text(summary))
+        .unwrap_or_default();
+    let mut tags: Vec<String> = props
+        .get("CATEGORIES")
+        .map(|(_, categories)| {
+            categories
+                .split(',')
+                .map(|category| unescape_ics_text(category).trim().to_owned())
+                .filter(|category| !category.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let name = if tags.is_empty() {
+        summary.to_owned()
+    } else {
+        tags.remove(0)
+    };
+    Some(ImportedSession {
+        name,
+        started_at,
+        finished_at,
+        tags,
+        note: Some(summary).filter(|summary| !summary.is_empty()),
+    })
+}
+
+fn unfold_ics_lines(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in content.lines() {
+        let line = line.trim_end_matches('\r');
+        match lines.last_mut() {
+            Some(last) if line.starts_with(' ') || line.starts_with('\t') => {
+                last.push_str(&line[1..]);
+            }
+            _ => lines.push(line.to_owned()),
+        }
+    }
+    lines
+}
+
+// all-day events carry a bare date and are not imported
+fn parse_ics_datetime(params: &str, value: &str) -> Option<i64> {
+    if let Some(utc_value) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc_value, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|dt| Utc.from_utc_datetime(&dt).timestamp());
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let time_zone = params
+        .split(';')
+        .find_map(|param| param.strip_prefix("TZID="))
+        .and_then(|tz| tz.trim_matches('"').parse::<Tz>().ok());
+    match time_zone {
+        Some(tz) => tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|dt| dt.timestamp()),
+        None => Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|dt| dt.timestamp()),
+    }
+}
+
+// e.g. PT1H30M or P1DT2H
+fn parse_ics_duration(value: &str) -> Option<i64> {
+    let value = value.strip_prefix('P')?;
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => continue,
+            unit => {
+                let amount: i64 = number.parse().ok()?;
+                number.clear();
+                seconds += amount
+                    * match unit {
+                        'W' => 7 * 24 * 3600,
+                        'D' => 24 * 3600,
+                        'H' => 3600,
+                        'M' => 60,
+                        'S' => 1,
+                        _ => return None,
+                    };
+            }
+        }
+    }
+    Some(seconds)
+}
+
+fn unescape_ics_text(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn parse_local_datetime(date: &str, time: &str) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let time = chrono::NaiveTime::parse_from_str(time, "%H:%M:%S").ok()?;
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(|dt| dt.timestamp())
+}
+
+fn parse_utc_timestamp(value: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|dt| Utc.from_utc_datetime(&dt).timestamp())
+}
+
+fn split_quoted(value: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in value.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    parts.push(current.clone());
+                    current.clear();
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
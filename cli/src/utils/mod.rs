@@ -1,3 +1,12 @@
 pub mod clock;
+pub mod color;
+pub mod completions;
+pub mod doctor;
 pub mod errors;
+pub mod export;
+pub mod import;
 pub mod printer;
+pub mod prompt;
+pub mod report;
+pub mod timer;
+pub mod tree;
@@ -1,9 +1,120 @@
+use std::fmt::Display;
 use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
 use tabwriter::TabWriter;
 
+use crate::utils::color::{Cyan, Red};
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(format: &str) -> Result<OutputFormat, String> {
+        match format {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output format '{}'", format)),
+        }
+    }
+}
+
+// the codes are part of the json output, so they must not change
+#[derive(Clone, Copy)]
+pub enum ErrorCode {
+    InvalidInput,
+    NotLoggedIn,
+    CommandFailed,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ErrorCode::InvalidInput => "invalid_input",
+            ErrorCode::NotLoggedIn => "not_logged_in",
+            ErrorCode::CommandFailed => "command_failed",
+        }
+    }
+
+    // usage mistakes exit with 2, like clap does for its own
+    fn exit_code(&self) -> i32 {
+        match self {
+            ErrorCode::InvalidInput => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorDocument<'a> {
+    error: ErrorBody<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+}
+
+pub fn set_output_format(format: OutputFormat) {
+    JSON_OUTPUT.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+pub fn output_format() -> OutputFormat {
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        OutputFormat::Json
+    } else {
+        OutputFormat::Text
+    }
+}
+
 pub fn write_tab_written_message(message: String) {
     let mut tw = TabWriter::new(vec![]);
     tw.write_all(message.as_bytes()).unwrap();
     tw.flush().unwrap();
     println!("{}", String::from_utf8(tw.into_inner().unwrap()).unwrap());
 }
+
+pub fn write_json_document<T: Serialize>(document: &T) {
+    println!("{}", serde_json::to_string_pretty(document).unwrap());
+}
+
+// progress and warnings go to stderr with json output, to keep stdout free
+// for the json document
+pub fn write_progress_message(message: String) {
+    match output_format() {
+        OutputFormat::Json => eprintln!("{}", message),
+        OutputFormat::Text => println!("{}", message),
+    }
+}
+
+// with json output stdout only ever carries the requested document, so the
+// error is written to stderr instead.
+pub fn exit_with_error<M: Display>(code: ErrorCode, message: M) -> ! {
+    match output_format() {
+        OutputFormat::Json => {
+            let message = message.to_string();
+            let document = ErrorDocument {
+                error: ErrorBody {
+                    code: code.as_str(),
+                    message: &message,
+                },
+            };
+            eprintln!("{}", serde_json::to_string(&document).unwrap());
+        }
+        OutputFormat::Text => {
+            println!(
+                "{} {}",
+                Red.paint("ERROR:"),
+                Cyan.paint(message.to_string())
+            );
+        }
+    }
+    std::process::exit(code.exit_code());
+}
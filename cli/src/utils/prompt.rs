@@ -0,0 +1,71 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind, Write};
+use std::path::PathBuf;
+use std::process;
+
+pub fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        Err(_) => false,
+    }
+}
+
+// opens the user's editor on a scratch file, lines starting with '#' are
+// treated as comments and dropped from the result.
+pub fn edit_message(template: &str) -> Result<String, String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let (path, mut file) = create_scratch_file()?;
+    file.write_all(template.as_bytes())
+        .map_err(|err| err.to_string())?;
+    drop(file);
+    let status = process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg(&editor)
+        .arg(&path)
+        .status();
+    let message = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(_) => return Err(format!("{} exited with an error", editor)),
+        Err(err) => return Err(format!("failed to launch {}: {}", editor, err)),
+    };
+    Ok(message
+        .map_err(|err| err.to_string())?
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<&str>>()
+        .join("\n")
+        .trim()
+        .to_owned())
+}
+
+// the temporary directory is shared with the other users, so the file gets a
+// name that cannot be guessed and is never one that already exists
+fn create_scratch_file() -> Result<(PathBuf, File), String> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    loop {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(process::id());
+        let path = env::temp_dir().join(format!("punch-message-{:016x}.txt", hasher.finish()));
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(format!("failed to create {}: {}", path.display(), err)),
+        }
+    }
+}
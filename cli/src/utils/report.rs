@@ -0,0 +1,130 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use std::env;
+
+use chrono::{Datelike, Duration, Local, LocalResult, NaiveDate, TimeZone};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ReportPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+pub struct ReportRow {
+    pub period_start: i64,
+    pub name: String,
+    pub duration: i64,
+}
+
+impl ReportPeriod {
+    pub fn parse(period: &str) -> Result<ReportPeriod, String> {
+        match period {
+            "day" => Ok(ReportPeriod::Day),
+            "week" => Ok(ReportPeriod::Week),
+            "month" => Ok(ReportPeriod::Month),
+            _ => Err(format!("unknown period '{}'", period)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ReportPeriod::Day => "day",
+            ReportPeriod::Week => "week",
+            ReportPeriod::Month => "month",
+        }
+    }
+
+    // weeks start on monday, like postgres' date_trunc does on the server
+    pub fn start_of(&self, ts: i64) -> i64 {
+        let date = local_date(ts);
+        let first_day = match self {
+            ReportPeriod::Day => date,
+            ReportPeriod::Week => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+            ReportPeriod::Month => date.with_day(1).unwrap(),
+        };
+        local_midnight(first_day)
+    }
+
+    pub fn next_start(&self, period_start: i64) -> i64 {
+        let date = local_date(period_start);
+        let next_day = match self {
+            ReportPeriod::Day => date + Duration::days(1),
+            ReportPeriod::Week => date + Duration::weeks(1),
+            ReportPeriod::Month => match date.month() {
+                12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1).unwrap(),
+                month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1).unwrap(),
+            },
+        };
+        local_midnight(next_day)
+    }
+
+    pub fn label(&self, period_start: i64) -> String {
+        let date = local_date(period_start);
+        match self {
+            ReportPeriod::Day => date.format("%Y-%m-%d %a").to_string(),
+            ReportPeriod::Week => date.format("%Y-%m-%d (week %V)").to_string(),
+            ReportPeriod::Month => date.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+// the server needs the zone name to cut periods the same way, TZ wins over
+// the system setting just like it does for chrono's Local.
+pub fn local_time_zone_name() -> String {
+    match env::var("TZ") {
+        Ok(tz) if !tz.trim_start_matches(':').is_empty() => tz.trim_start_matches(':').to_owned(),
+        _ => iana_time_zone::get_timezone().unwrap_or_else(|_| String::from("UTC")),
+    }
+}
+
+// sessions are (name, started_at, finished_at), the ones crossing a period
+// boundary are split so each period only gets the time spent inside of it.
+pub fn split_into_periods(
+    sessions: &[(String, i64, i64)],
+    period: ReportPeriod,
+    since: i64,
+    until: i64,
+) -> Vec<ReportRow> {
+    let mut totals: BTreeMap<(i64, String), i64> = BTreeMap::new();
+    let mut period_start = period.start_of(since);
+    while period_start < until {
+        let period_end = period.next_start(period_start);
+        for (name, started_at, finished_at) in sessions {
+            let duration = cmp::min(*finished_at, cmp::min(period_end, until))
+                - cmp::max(*started_at, cmp::max(period_start, since));
+            if duration > 0 {
+                *totals.entry((period_start, name.to_owned())).or_insert(0) += duration;
+            }
+        }
+        period_start = period_end;
+    }
+    totals
+        .into_iter()
+        .map(|((period_start, name), duration)| ReportRow {
+            period_start,
+            name,
+            duration,
+        })
+        .collect()
+}
+
+fn local_date(ts: i64) -> NaiveDate {
+    Local.timestamp_opt(ts, 0).unwrap().date_naive()
+}
+
+fn local_midnight(date: NaiveDate) -> i64 {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    match Local.from_local_datetime(&midnight) {
+        LocalResult::Single(dt) => dt.timestamp(),
+        LocalResult::Ambiguous(earliest, _) => earliest.timestamp(),
+        // midnight was skipped by a DST change, the day starts an hour later
+        LocalResult::None => Local
+            .from_local_datetime(&(midnight + Duration::hours(1)))
+            .earliest()
+            .unwrap()
+            .timestamp(),
+    }
+}
@@ -0,0 +1,47 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::utils::clock::seconds_to_duration;
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+// ctrl-c only raises the flag, the countdown notices it and the caller
+// decides what happens to the session in progress.
+pub fn interrupt_flag() -> Result<Arc<AtomicBool>, String> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let handler_flag = interrupted.clone();
+    ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))
+        .map_err(|err| err.to_string())?;
+    Ok(interrupted)
+}
+
+// redraws the remaining time on a single line of stderr, which keeps stdout
+// for the json document. returns false when it was interrupted before the
+// time was up.
+pub fn countdown(label: &str, seconds: i64, interrupted: &AtomicBool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(seconds as u64);
+    let mut line_width = 0;
+    let finished = loop {
+        if interrupted.load(Ordering::SeqCst) {
+            break false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break true;
+        }
+        let left = deadline - now;
+        // rounds up, so the last second shows as 00:00:01 instead of zero
+        let left_secs = left.as_secs() as i64 + i64::from(left.subsec_nanos() > 0);
+        let line = format!("{} {} left", label, seconds_to_duration(left_secs));
+        line_width = line_width.max(line.chars().count());
+        eprint!("\r{:width$}", line, width = line_width);
+        let _ = io::stderr().flush();
+        thread::sleep(REFRESH_INTERVAL.min(left));
+    };
+    eprint!("\r{}\r", " ".repeat(line_width));
+    let _ = io::stderr().flush();
+    finished
+}
@@ -0,0 +1,86 @@
+use crate::puncher::TaskListItem;
+
+pub const TASK_PATH_SEPARATOR: char = '/';
+
+pub struct TaskTreeNode {
+    pub name: String,
+    pub duration: i64,
+    pub current: i64,
+    pub in_progress: bool,
+    pub children: Vec<TaskTreeNode>,
+}
+
+impl TaskTreeNode {
+    fn new(name: &str) -> TaskTreeNode {
+        TaskTreeNode {
+            name: name.to_owned(),
+            duration: 0,
+            current: 0,
+            in_progress: false,
+            children: vec![],
+        }
+    }
+
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| a.name.cmp(&b.name));
+        for child in self.children.iter_mut() {
+            child.sort();
+        }
+    }
+}
+
+// every task adds its time to each node along its path, so a node holds the
+// subtotal of everything nested under it.
+pub fn build_task_tree(tasks: &[TaskListItem], now_ts: i64) -> Vec<TaskTreeNode> {
+    let mut root = TaskTreeNode::new("");
+    for task in tasks {
+        let current = match task.finished_at {
+            Some(_) => 0,
+            None => now_ts - task.started_at,
+        };
+        let mut node = &mut root;
+        for segment in task
+            .name
+            .split(TASK_PATH_SEPARATOR)
+            .filter(|segment| !segment.is_empty())
+        {
+            let idx = match node.children.iter().position(|child| child.name == segment) {
+                Some(idx) => idx,
+                None => {
+                    node.children.push(TaskTreeNode::new(segment));
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[idx];
+            node.duration += task.duration;
+            node.current += current;
+            node.in_progress = node.in_progress || task.finished_at.is_none();
+        }
+    }
+    root.sort();
+    root.children
+}
+
+pub fn flatten_task_tree(nodes: &[TaskTreeNode], depth: usize) -> Vec<(usize, &TaskTreeNode)> {
+    let mut flattened = vec![];
+    for node in nodes {
+        flattened.push((depth, node));
+        flattened.extend(flatten_task_tree(&node.children, depth + 1));
+    }
+    flattened
+}
+
+// `acme/` refers to the `acme` task and everything nested under it.
+pub fn task_prefix(task_name: &str) -> Option<&str> {
+    task_name
+        .strip_suffix(TASK_PATH_SEPARATOR)
+        .filter(|prefix| !prefix.is_empty())
+}
+
+pub fn is_under_prefix(task_name: &str, prefix: &str) -> bool {
+    task_name == prefix
+        || matches!(
+            task_name.strip_prefix(prefix),
+            Some(rest) if rest.starts_with(TASK_PATH_SEPARATOR)
+        )
+}
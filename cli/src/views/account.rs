@@ -0,0 +1,164 @@
+use std::env;
+
+use serde::Serialize;
+
+use crate::puncher::{SyncReport, UploadReport};
+use crate::utils::{
+    clock::seconds_to_duration,
+    color::{Cyan, Green, Red, Yellow},
+    printer::{output_format, write_json_document, write_tab_written_message, OutputFormat},
+};
+
+// the sessions recorded locally are only uploaded after asking, with json
+// output they are left for migrate-to-server
+pub fn show_logged_in(sessions_to_upload: usize) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&LoginDocument {
+            logged_in: true,
+            sessions_to_upload,
+        });
+        return;
+    }
+    println!("{}", Green.paint("successfully logged in"));
+}
+
+pub fn show_logged_out() {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&LogoutDocument { logged_in: false });
+        return;
+    }
+    println!("{}", Green.paint("successfully logged out"));
+}
+
+pub fn show_upload(report: &UploadReport) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&UploadDocument {
+            uploaded: report.uploaded,
+            skipped: report.skipped,
+            duration: report.duration,
+        });
+        return;
+    }
+    if report.uploaded == 0 && report.skipped == 0 {
+        println!("no local sessions left to upload");
+        return;
+    }
+    write_tab_written_message(format!(
+        "{}\n{}\t{}\t{}",
+        Cyan.paint("uploaded\tskipped\ttime uploaded"),
+        Green.paint(report.uploaded.to_string()),
+        report.skipped,
+        Yellow.paint(seconds_to_duration(report.duration)),
+    ));
+}
+
+// the rejected punches were already warned about when they were synced
+pub fn show_sync(report: &SyncReport) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&SyncDocument {
+            synced: report.synced,
+            pending: report.pending,
+            rejected: &report.rejected,
+        });
+        return;
+    }
+    println!(
+        "{} punch(es) synced",
+        Green.paint(report.synced.to_string())
+    );
+    if report.pending > 0 {
+        println!(
+            "{} punch(es) still waiting for the server",
+            Yellow.paint(report.pending.to_string())
+        );
+    }
+}
+
+pub fn show_account_use(name: &str) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&AccountUseDocument { account: name });
+        return;
+    }
+    println!(
+        "{} account {}",
+        Green.paint("switched to"),
+        Cyan.paint(name)
+    );
+    if let Some(env_profile) = env::var("PUNCH_PROFILE").ok().filter(|p| p != name) {
+        println!(
+            "{} PUNCH_PROFILE is set to {} and still takes precedence",
+            Yellow.paint("warning:"),
+            env_profile
+        );
+    }
+}
+
+pub fn show_account_list(active: &str, accounts: Vec<AccountDocument>) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&AccountListDocument {
+            active_account: active,
+            accounts,
+        });
+        return;
+    }
+    let mut message = format!("{}", Cyan.paint("\taccount\tendpoint\ttoken"));
+    for account in accounts.iter() {
+        let token = match account.token {
+            "valid" => Green.paint(account.token).to_string(),
+            "expired" | "invalid" => Red.paint(account.token).to_string(),
+            "unverified" => Yellow.paint(account.token).to_string(),
+            _ => account.token.to_owned(),
+        };
+        message.push_str(&format!(
+            "\n{}\t{}\t{}\t{}",
+            if account.name == active { "*" } else { "" },
+            account.name,
+            account.endpoint,
+            token
+        ));
+    }
+    write_tab_written_message(message);
+}
+
+#[derive(Serialize)]
+struct LoginDocument {
+    logged_in: bool,
+    sessions_to_upload: usize,
+}
+
+#[derive(Serialize)]
+struct LogoutDocument {
+    logged_in: bool,
+}
+
+#[derive(Serialize)]
+struct UploadDocument {
+    uploaded: usize,
+    skipped: usize,
+    duration: i64,
+}
+
+#[derive(Serialize)]
+struct SyncDocument<'a> {
+    synced: usize,
+    pending: usize,
+    rejected: &'a [String],
+}
+
+#[derive(Serialize)]
+struct AccountUseDocument<'a> {
+    account: &'a str,
+}
+
+#[derive(Serialize)]
+struct AccountListDocument<'a> {
+    active_account: &'a str,
+    accounts: Vec<AccountDocument<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct AccountDocument<'a> {
+    pub name: &'a str,
+    pub endpoint: String,
+    pub token: &'static str,
+}
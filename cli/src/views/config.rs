@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::managers::configs::{Profile, PROFILE_KEYS};
+use crate::utils::{
+    color::{Cyan, Green, Purple, Red, Yellow},
+    doctor::{Check, CheckStatus},
+    printer::{
+        exit_with_error, output_format, write_json_document, write_tab_written_message, ErrorCode,
+        OutputFormat,
+    },
+};
+
+pub fn show_config_value(profile: &str, key: &str, value: Option<&str>) {
+    match value {
+        value if output_format() == OutputFormat::Json => {
+            write_json_document(&ConfigValueDocument {
+                profile,
+                key,
+                value,
+            });
+        }
+        Some(value) => println!("{}", value),
+        None => {
+            exit_with_error(
+                ErrorCode::CommandFailed,
+                format!("{} is not set in profile '{}'", key, profile),
+            );
+        }
+    }
+}
+
+// the default profile is set for the whole file, not in a profile
+pub fn show_config_saved(profile: &str, key: &str, saved: Option<&str>, in_profile: bool) {
+    let of_profile = |preposition: &str| {
+        if in_profile {
+            format!(" {} profile {}", preposition, Cyan.paint(profile))
+        } else {
+            String::new()
+        }
+    };
+    match saved {
+        saved if output_format() == OutputFormat::Json => {
+            write_json_document(&ConfigValueDocument {
+                profile,
+                key,
+                value: saved,
+            });
+        }
+        Some(saved) => println!(
+            "{} {} to {}{}",
+            Green.paint("set"),
+            key,
+            Cyan.paint(saved),
+            of_profile("in")
+        ),
+        None => println!("{} {}{}", Yellow.paint("removed"), key, of_profile("from")),
+    }
+}
+
+pub fn show_config_list(
+    path: &str,
+    default_profile: &str,
+    active_profile: &str,
+    profiles: &BTreeMap<String, Profile>,
+) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&ConfigListDocument {
+            path,
+            default_profile,
+            active_profile,
+            profiles,
+        });
+        return;
+    }
+    if profiles.is_empty() {
+        println!(
+            "no profiles in {} yet, start with punch config set endpoint <URL>",
+            path
+        );
+        return;
+    }
+    let mut message = format!(
+        "{}",
+        Cyan.paint(format!("\tprofile\t{}", PROFILE_KEYS.join("\t")))
+    );
+    for (name, settings) in profiles.iter() {
+        message.push_str(&format!(
+            "\n{}\t{}",
+            if name == active_profile { "*" } else { "" },
+            name
+        ));
+        for key in PROFILE_KEYS {
+            message.push_str(&format!(
+                "\t{}",
+                settings.get(key).unwrap().unwrap_or_default()
+            ));
+        }
+    }
+    write_tab_written_message(message);
+}
+
+pub fn show_checks(checks: &[Check], failed: usize) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&DoctorDocument {
+            healthy: failed == 0,
+            checks,
+        });
+        return;
+    }
+    for check in checks.iter() {
+        let status = format!("{:<4}", check.status.as_str());
+        let status = match check.status {
+            CheckStatus::Pass => Green.paint(status),
+            CheckStatus::Warn => Yellow.paint(status),
+            CheckStatus::Fail => Red.paint(status),
+            CheckStatus::Skip => Purple.paint(status),
+        };
+        println!("[{}] {:<13} {}", status, check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("{:>21}{}", "", Cyan.paint(hint));
+        }
+    }
+    match failed {
+        0 => println!("{}", Green.paint("no problems found")),
+        _ => println!("{}", Red.paint(format!("{} check(s) failed", failed))),
+    }
+}
+
+#[derive(Serialize)]
+struct ConfigValueDocument<'a> {
+    profile: &'a str,
+    key: &'a str,
+    value: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct ConfigListDocument<'a> {
+    path: &'a str,
+    default_profile: &'a str,
+    active_profile: &'a str,
+    profiles: &'a BTreeMap<String, Profile>,
+}
+
+#[derive(Serialize)]
+struct DoctorDocument<'a> {
+    healthy: bool,
+    checks: &'a [Check],
+}
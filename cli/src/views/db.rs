@@ -0,0 +1,281 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::database::{
+    aggregate::CompactReport, maintenance::DatabaseProblems, merge::MergeReport,
+    migrations::AppliedMigration, task::Task,
+};
+use crate::utils::{
+    clock::utc_ts_to_local_datetime,
+    color::{Cyan, Green, Red, Yellow},
+    printer::{output_format, write_json_document, write_tab_written_message, OutputFormat},
+};
+
+pub struct DatabaseStatus<'a> {
+    pub path: &'a Path,
+    pub size: u64,
+    pub sessions: i64,
+    pub compacted_days: i64,
+    pub encrypted: bool,
+    pub migrations: &'a [AppliedMigration],
+}
+
+pub fn show_db_status(status: &DatabaseStatus) {
+    let path = status.path.display().to_string();
+    if output_format() == OutputFormat::Json {
+        write_json_document(&DbStatusDocument {
+            path: &path,
+            size: status.size,
+            sessions: status.sessions,
+            compacted_days: status.compacted_days,
+            encrypted: status.encrypted,
+            schema_version: status
+                .migrations
+                .last()
+                .map(|migration| migration.version.as_str()),
+            migrations: status
+                .migrations
+                .iter()
+                .map(|migration| MigrationDocument {
+                    version: &migration.version,
+                    applied_at: migration.run_on,
+                })
+                .collect(),
+        });
+        return;
+    }
+    write_tab_written_message(format!(
+        "{}\n{}\t{}\t{}\t{}\t{}",
+        Cyan.paint("path\tsize\tsessions\tcompacted days\tencrypted"),
+        path,
+        format_size(status.size),
+        status.sessions,
+        status.compacted_days,
+        if status.encrypted { "yes" } else { "no" }
+    ));
+    println!();
+    let mut message = Cyan.paint("migration\tapplied at").to_string();
+    for migration in status.migrations.iter() {
+        message.push_str(&format!(
+            "\n{}\t{}",
+            migration.version,
+            Green.paint(utc_ts_to_local_datetime(migration.run_on))
+        ));
+    }
+    write_tab_written_message(message);
+}
+
+pub fn show_db_check(problems: &DatabaseProblems) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&DbCheckDocument {
+            healthy: problems.is_empty(),
+            integrity: &problems.integrity,
+            reversed_sessions: session_refs(&problems.reversed_sessions),
+            duplicate_open_sessions: session_refs(&problems.duplicate_open_sessions),
+        });
+        return;
+    }
+    print_database_problems(problems);
+}
+
+pub fn show_vacuum(path: &Path, size_before: u64, size_after: u64) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&DbVacuumDocument {
+            path: &path.display().to_string(),
+            size_before,
+            size_after,
+        });
+        return;
+    }
+    println!(
+        "{} {} from {} to {}",
+        Green.paint("vacuumed"),
+        path.display(),
+        format_size(size_before),
+        format_size(size_after)
+    );
+}
+
+pub fn show_compact(before: i64, report: &CompactReport) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&DbCompactDocument {
+            before,
+            sessions: report.sessions,
+            days: report.aggregates,
+        });
+        return;
+    }
+    println!(
+        "{} {} session(s) into {} new daily total(s)",
+        Green.paint("compacted"),
+        report.sessions,
+        report.aggregates
+    );
+}
+
+pub fn show_encryption(path: &Path, encrypted: bool) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&DbEncryptionDocument {
+            path: &path.display().to_string(),
+            encrypted,
+        });
+        return;
+    }
+    if encrypted {
+        println!(
+            "{} {}, its key is in the keyring and the database cannot be read without it",
+            Green.paint("encrypted"),
+            path.display()
+        );
+    } else {
+        println!("{} {}", Green.paint("decrypted"), path.display());
+    }
+}
+
+// the problems were printed before asking to repair them
+pub fn show_repair(report: &DbRepairDocument, had_problems: bool) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(report);
+    } else if had_problems {
+        println!(
+            "{} {} swapped, {} finished",
+            Green.paint("repaired"),
+            report.swapped_sessions,
+            report.finished_sessions
+        );
+    }
+}
+
+pub fn show_merge(report: &MergeReport, merged_path: &Path) {
+    println!(
+        "{} {} session(s) and {} punch(es) waiting for a sync, skipped {} already recorded, the old database was renamed to {}",
+        Green.paint("merged"),
+        report.sessions,
+        report.operations,
+        report.skipped,
+        merged_path.display()
+    );
+}
+
+pub fn print_database_problems(problems: &DatabaseProblems) {
+    if problems.is_empty() {
+        println!("{}", Green.paint("no problems found"));
+        return;
+    }
+    for message in problems.integrity.iter() {
+        println!("{} {}", Red.paint("DAMAGED:"), message);
+    }
+    let mut message = Cyan
+        .paint("id\tname\tstarted at\tfinished at\tproblem")
+        .to_string();
+    let sessions = problems
+        .reversed_sessions
+        .iter()
+        .map(|session| (session, "finished before it started"))
+        .chain(
+            problems
+                .duplicate_open_sessions
+                .iter()
+                .map(|session| (session, "in progress more than once")),
+        );
+    let mut has_sessions = false;
+    for (session, problem) in sessions {
+        has_sessions = true;
+        message.push_str(&format!(
+            "\n{}\t{}\t{}\t{}\t{}",
+            session.id,
+            session.name,
+            utc_ts_to_local_datetime(session.started_at),
+            session
+                .finished_at
+                .map(utc_ts_to_local_datetime)
+                .unwrap_or_else(|| String::from("-")),
+            Yellow.paint(problem)
+        ));
+    }
+    if has_sessions {
+        write_tab_written_message(message);
+    }
+}
+
+fn session_refs(sessions: &[Task]) -> Vec<SessionRefDocument<'_>> {
+    sessions
+        .iter()
+        .map(|session| SessionRefDocument {
+            id: session.id,
+            name: &session.name,
+            started_at: session.started_at,
+            finished_at: session.finished_at,
+        })
+        .collect()
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
+
+#[derive(Serialize)]
+struct DbStatusDocument<'a> {
+    path: &'a str,
+    size: u64,
+    sessions: i64,
+    compacted_days: i64,
+    encrypted: bool,
+    schema_version: Option<&'a str>,
+    migrations: Vec<MigrationDocument<'a>>,
+}
+
+#[derive(Serialize)]
+struct MigrationDocument<'a> {
+    version: &'a str,
+    applied_at: i64,
+}
+
+#[derive(Serialize)]
+struct DbCheckDocument<'a> {
+    healthy: bool,
+    integrity: &'a [String],
+    reversed_sessions: Vec<SessionRefDocument<'a>>,
+    duplicate_open_sessions: Vec<SessionRefDocument<'a>>,
+}
+
+#[derive(Serialize)]
+struct SessionRefDocument<'a> {
+    id: i32,
+    name: &'a str,
+    started_at: i64,
+    finished_at: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct DbVacuumDocument<'a> {
+    path: &'a str,
+    size_before: u64,
+    size_after: u64,
+}
+
+#[derive(Serialize)]
+struct DbCompactDocument {
+    before: i64,
+    sessions: usize,
+    days: usize,
+}
+
+#[derive(Serialize)]
+struct DbEncryptionDocument<'a> {
+    path: &'a str,
+    encrypted: bool,
+}
+
+#[derive(Serialize)]
+pub struct DbRepairDocument {
+    pub swapped_sessions: usize,
+    pub finished_sessions: usize,
+    // damage found by sqlite, which needs a backup
+    pub unrepaired: usize,
+}
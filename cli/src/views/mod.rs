@@ -0,0 +1,19 @@
+pub mod account;
+pub mod config;
+pub mod db;
+pub mod tasks;
+
+use crate::utils::{
+    color::Yellow,
+    printer::{output_format, OutputFormat},
+};
+
+// the json documents carry the same information in their offline field
+pub fn print_offline_notice(offline: bool) {
+    if offline && output_format() == OutputFormat::Text {
+        println!(
+            "{} the server is unreachable, punches are kept locally until the next sync",
+            Yellow.paint("OFFLINE:")
+        );
+    }
+}
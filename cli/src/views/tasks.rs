@@ -0,0 +1,687 @@
+use serde::Serialize;
+
+use crate::database::{
+    tag::normalize_tags,
+    task::{get_ts, Task},
+};
+use crate::puncher::{ImportReport, SwitchReport, TaskListItem, TaskPolicy, TaskSession, TaskStat};
+use crate::utils::{
+    clock::{seconds_to_duration, utc_ts_to_local_datetime},
+    color::{Cyan, Green, Purple, Red, Yellow},
+    printer::{output_format, write_json_document, write_tab_written_message, OutputFormat},
+    report::{ReportPeriod, ReportRow},
+    tree::{build_task_tree, flatten_task_tree},
+};
+use crate::views::print_offline_notice;
+
+pub fn show_punch_in(name: &str, started_at: i64, tags: &[String], offline: bool) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&PunchInDocument {
+            name,
+            started_at,
+            tags: normalize_tags(tags),
+            offline,
+        });
+        return;
+    }
+    write_tab_written_message(format!(
+        "{}\n{}\t{}\t{}",
+        Cyan.paint("name\tstarted at\ttags"),
+        name,
+        Green.paint(utc_ts_to_local_datetime(started_at)),
+        tags.join(" "),
+    ));
+    print_offline_notice(offline);
+}
+
+pub fn show_punch_out(name: &str, task: &Task, offline: bool) {
+    // TODO: this needs better error handling
+    let ts = task.finished_at.unwrap();
+    if output_format() == OutputFormat::Json {
+        write_json_document(&PunchOutDocument {
+            name,
+            started_at: task.started_at,
+            finished_at: ts,
+            duration: ts - task.started_at,
+            note: task.note.as_deref(),
+            offline,
+        });
+        return;
+    }
+    write_tab_written_message(format!(
+        "{}\n{}\t{}\t{}\t{}",
+        Cyan.paint("name\tfinished at\ttime spent\tnote"),
+        name,
+        Green.paint(utc_ts_to_local_datetime(ts)),
+        Yellow.paint(seconds_to_duration(ts - task.started_at)),
+        format_note(&task.note),
+    ));
+    print_offline_notice(offline);
+}
+
+pub fn show_switch(name: &str, report: &SwitchReport, tags: &[String], offline: bool) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&SwitchDocument {
+            stopped: report
+                .stopped
+                .iter()
+                .map(|task| StoppedTaskDocument {
+                    name: &task.name,
+                    started_at: task.started_at,
+                    finished_at: report.started_at,
+                    duration: report.started_at - task.started_at,
+                })
+                .collect(),
+            name,
+            started_at: report.started_at,
+            tags: normalize_tags(tags),
+            offline,
+        });
+        return;
+    }
+    if !report.stopped.is_empty() {
+        let mut message = format!("{}", Cyan.paint("name\tfinished at\ttime spent"));
+        for task in report.stopped.iter() {
+            message.push_str(&format!(
+                "\n{}\t{}\t{}",
+                task.name,
+                Green.paint(utc_ts_to_local_datetime(report.started_at)),
+                Yellow.paint(seconds_to_duration(report.started_at - task.started_at)),
+            ));
+        }
+        write_tab_written_message(message);
+    }
+    write_tab_written_message(format!(
+        "{}\n{}\t{}\t{}",
+        Cyan.paint("name\tstarted at\ttags"),
+        name,
+        Green.paint(utc_ts_to_local_datetime(report.started_at)),
+        tags.join(" "),
+    ));
+    print_offline_notice(offline);
+}
+
+// a session that was added or edited
+pub fn show_session(task: &Task, offline: bool) {
+    let now_ts = get_ts().unwrap().as_secs() as i64;
+    let duration = task.finished_at.unwrap_or(now_ts) - task.started_at;
+    if output_format() == OutputFormat::Json {
+        write_json_document(&SessionDocument {
+            id: task.id as i64,
+            name: &task.name,
+            started_at: task.started_at,
+            finished_at: task.finished_at,
+            duration,
+            note: task.note.as_deref(),
+            offline,
+        });
+        return;
+    }
+    write_tab_written_message(format!(
+        "{}\n{}\t{}\t{}\t{}\t{}",
+        Cyan.paint("id\tname\tstarted at\tfinished at\ttime spent"),
+        task.id,
+        task.name,
+        Green.paint(utc_ts_to_local_datetime(task.started_at)),
+        match task.finished_at {
+            Some(ts) => Green.paint(utc_ts_to_local_datetime(ts)),
+            None => Red.paint("in progress"),
+        },
+        Yellow.paint(seconds_to_duration(duration)),
+    ));
+    print_offline_notice(offline);
+}
+
+pub fn show_cancelled(name: &str, offline: bool) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&CancelDocument {
+            name,
+            cancelled: true,
+            offline,
+        });
+        return;
+    }
+    println!("Cancelled {}", Cyan.paint(name));
+    print_offline_notice(offline);
+}
+
+pub fn show_task_list(tasks: &[TaskListItem], as_tree: bool, offline: bool) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&task_list_document(tasks, offline));
+        return;
+    }
+    if as_tree {
+        print_task_tree(tasks);
+        print_offline_notice(offline);
+        return;
+    }
+    write_tab_written_message(
+        tasks
+            .iter()
+            .map(|task| {
+                let duration = Purple.paint(seconds_to_duration(task.duration));
+                return match task.finished_at {
+                    Some(_) => {
+                        format!(
+                            "{}\t({})\t{}\t{}\t{}\t{}",
+                            task.name,
+                            Green.paint("complete"),
+                            String::new(),
+                            duration,
+                            task.pomodoros,
+                            format_tags(&task.tags),
+                        )
+                    }
+                    None => {
+                        let now_dt = get_ts().unwrap();
+                        let now_ts = now_dt.as_secs() as i64;
+                        format!(
+                            "{}\t({})\t{}\t{}\t{}\t{}",
+                            task.name,
+                            Red.paint("in progress"),
+                            Yellow.paint(seconds_to_duration(now_ts - task.started_at)),
+                            duration,
+                            task.pomodoros,
+                            format_tags(&task.tags),
+                        )
+                    }
+                };
+            })
+            .fold(
+                Cyan.paint(
+                    "name\tstatus\tcurrent total\ttotal (minus current total)\tpomodoros\ttags\n",
+                )
+                .to_string(),
+                |a, b| a + &b + "\n",
+            ),
+    );
+    print_offline_notice(offline);
+}
+
+pub fn show_task_stat(stat: &TaskStat, since: i64, until: i64, offline: bool) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&TaskStatDocument {
+            name: &stat.name,
+            in_progress: stat.status == "in progress",
+            duration: stat.duration,
+            pomodoros: stat.pomodoros,
+            since,
+            until,
+            offline,
+        });
+        return;
+    }
+    write_tab_written_message(format!(
+        "{}\n{}\t({})\t{}\t{}",
+        Cyan.paint("name\tstatus\ttime spent\tpomodoros"),
+        stat.name,
+        if stat.status == "in progress" {
+            Red.paint("in progress")
+        } else {
+            Green.paint("complete")
+        },
+        Yellow.paint(seconds_to_duration(stat.duration)),
+        stat.pomodoros,
+    ));
+    print_offline_notice(offline);
+}
+
+pub fn show_task_policy(policy: TaskPolicy, offline: bool) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&TaskPolicyDocument {
+            task_policy: policy.as_str(),
+            offline,
+        });
+        return;
+    }
+    println!("task policy: {}", Cyan.paint(policy.as_str()));
+    print_offline_notice(offline);
+}
+
+pub fn show_status(tasks: &[TaskListItem], task_name: Option<&str>, offline: bool) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&task_list_document(tasks, offline));
+        return;
+    }
+    print_status(tasks, task_name);
+    print_offline_notice(offline);
+}
+
+pub fn show_sessions(sessions: &[TaskSession], offline: bool) {
+    let now_ts = get_ts().unwrap().as_secs() as i64;
+    if output_format() == OutputFormat::Json {
+        write_json_document(&SessionListDocument {
+            sessions: sessions
+                .iter()
+                .map(|session| LoggedSessionDocument {
+                    id: session.id,
+                    name: &session.name,
+                    started_at: session.started_at,
+                    finished_at: session.finished_at,
+                    duration: session.finished_at.unwrap_or(now_ts) - session.started_at,
+                    tags: &session.tags,
+                    note: session.note.as_deref(),
+                })
+                .collect(),
+            offline,
+        });
+        return;
+    }
+    write_tab_written_message(
+        sessions
+            .iter()
+            .map(|session| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    session.id,
+                    session.name,
+                    Green.paint(utc_ts_to_local_datetime(session.started_at)),
+                    match session.finished_at {
+                        Some(ts) => Green.paint(utc_ts_to_local_datetime(ts)),
+                        None => Red.paint("in progress"),
+                    },
+                    Yellow.paint(seconds_to_duration(
+                        session.finished_at.unwrap_or(now_ts) - session.started_at
+                    )),
+                    format_tags(&session.tags),
+                    format_note(&session.note),
+                )
+            })
+            .fold(
+                Cyan.paint("id\tname\tstarted at\tfinished at\ttime spent\ttags\tnote\n")
+                    .to_string(),
+                |a, b| a + &b + "\n",
+            ),
+    );
+    print_offline_notice(offline);
+}
+
+pub struct ReportRange {
+    pub period: ReportPeriod,
+    pub by_day: bool,
+    pub since: i64,
+    pub until: i64,
+}
+
+pub fn show_report(rows: &[ReportRow], range: &ReportRange, offline: bool) {
+    let grouped = group_report_rows(rows, range.by_day);
+    if output_format() == OutputFormat::Json {
+        write_json_document(&ReportDocument {
+            period: range.period.as_str(),
+            group_by: if range.by_day { "day" } else { "task" },
+            since: range.since,
+            until: range.until,
+            rows: grouped
+                .iter()
+                .map(|(key, label, duration)| ReportRowDocument {
+                    period_start: range.period.start_of(*key),
+                    day: Some(*key).filter(|_| range.by_day),
+                    task: Some(label.as_str()).filter(|_| !range.by_day),
+                    duration: *duration,
+                })
+                .collect(),
+            duration: grouped.iter().map(|(_, _, duration)| duration).sum(),
+            offline,
+        });
+        return;
+    }
+    print_period_report(&grouped, range.period, range.by_day);
+    print_offline_notice(offline);
+}
+
+pub fn show_import(report: &ImportReport, ignored: usize, offline: bool) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(&ImportDocument {
+            imported: report.imported,
+            skipped: report.skipped,
+            ignored,
+            duration: report.duration,
+            offline,
+        });
+        return;
+    }
+    write_tab_written_message(format!(
+        "{}\n{}\t{}\t{}\t{}",
+        Cyan.paint("imported\tskipped\tignored\ttime imported"),
+        Green.paint(report.imported.to_string()),
+        report.skipped,
+        ignored,
+        Yellow.paint(seconds_to_duration(report.duration)),
+    ));
+    print_offline_notice(offline);
+}
+
+pub fn show_timer(document: &TimerDocument) {
+    if output_format() == OutputFormat::Json {
+        write_json_document(document);
+        return;
+    }
+    println!(
+        "{} pomodoro(s) completed on {}",
+        Green.paint(document.completed.to_string()),
+        Cyan.paint(document.name)
+    );
+    print_offline_notice(document.offline);
+}
+
+fn format_tags(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("+{}", tag))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// notes can span several lines, which would break the table alignment
+fn format_note(note: &Option<String>) -> String {
+    match note {
+        Some(note) => note.lines().collect::<Vec<&str>>().join(" / "),
+        None => String::new(),
+    }
+}
+
+// rows come in per task, or per day when grouping by day, ordered by period.
+// the days are summed over their tasks.
+fn group_report_rows(rows: &[ReportRow], by_day: bool) -> Vec<(i64, String, i64)> {
+    let mut grouped: Vec<(i64, String, i64)> = vec![];
+    for row in rows {
+        let (label, key) = if by_day {
+            (ReportPeriod::Day.label(row.period_start), row.period_start)
+        } else {
+            (row.name.to_owned(), row.period_start)
+        };
+        match grouped.last_mut() {
+            Some((last_key, _, duration)) if by_day && *last_key == key => {
+                *duration += row.duration;
+            }
+            _ => grouped.push((key, label, row.duration)),
+        }
+    }
+    grouped
+}
+
+fn print_period_report(grouped: &[(i64, String, i64)], period: ReportPeriod, by_day: bool) {
+    let mut lines = vec![Cyan
+        .paint(format!(
+            "{}\t{}\ttime spent",
+            period.as_str(),
+            if by_day { "day" } else { "task" }
+        ))
+        .to_string()];
+    let mut total = 0;
+    let mut current_period = None;
+    let mut period_total = 0;
+    for (key, label, duration) in grouped.iter() {
+        let row_period = period.start_of(*key);
+        if current_period != Some(row_period) {
+            if current_period.is_some() {
+                lines.push(format!(
+                    "\t{}\t{}",
+                    Cyan.paint("total"),
+                    Purple.paint(seconds_to_duration(period_total))
+                ));
+            }
+            current_period = Some(row_period);
+            period_total = 0;
+            lines.push(format!(
+                "{}\t{}\t{}",
+                period.label(row_period),
+                label,
+                Yellow.paint(seconds_to_duration(*duration))
+            ));
+        } else {
+            lines.push(format!(
+                "\t{}\t{}",
+                label,
+                Yellow.paint(seconds_to_duration(*duration))
+            ));
+        }
+        period_total += duration;
+        total += duration;
+    }
+    if current_period.is_some() {
+        lines.push(format!(
+            "\t{}\t{}",
+            Cyan.paint("total"),
+            Purple.paint(seconds_to_duration(period_total))
+        ));
+    }
+    lines.push(format!(
+        "{}\t\t{}",
+        Cyan.paint("all periods"),
+        Green.paint(seconds_to_duration(total))
+    ));
+    write_tab_written_message(lines.join("\n"));
+}
+
+fn print_status(tasks: &[TaskListItem], task_name: Option<&str>) {
+    if tasks.is_empty() {
+        match task_name {
+            Some(task_name) => println!("{} is not in progress", Cyan.paint(task_name)),
+            None => println!("no task in progress"),
+        }
+        return;
+    }
+    let now_ts = get_ts().unwrap().as_secs() as i64;
+    write_tab_written_message(
+        tasks
+            .iter()
+            .map(|task| {
+                format!(
+                    "{}\t{}\t{}\t{}",
+                    task.name,
+                    Green.paint(utc_ts_to_local_datetime(task.started_at)),
+                    Yellow.paint(seconds_to_duration(now_ts - task.started_at)),
+                    format_tags(&task.tags),
+                )
+            })
+            .fold(
+                Cyan.paint("name\tstarted at\tcurrent total\ttags\n")
+                    .to_string(),
+                |a, b| a + &b + "\n",
+            ),
+    );
+}
+
+fn print_task_tree(tasks: &[TaskListItem]) {
+    let now_ts = get_ts().unwrap().as_secs() as i64;
+    let tree = build_task_tree(tasks, now_ts);
+    write_tab_written_message(
+        flatten_task_tree(&tree, 0)
+            .iter()
+            .map(|(depth, node)| {
+                format!(
+                    "{}{}\t({})\t{}\t{}",
+                    "  ".repeat(*depth),
+                    node.name,
+                    if node.in_progress {
+                        Red.paint("in progress")
+                    } else {
+                        Green.paint("complete")
+                    },
+                    if node.in_progress {
+                        Yellow.paint(seconds_to_duration(node.current))
+                    } else {
+                        Yellow.paint(String::new())
+                    },
+                    Purple.paint(seconds_to_duration(node.duration)),
+                )
+            })
+            .fold(
+                Cyan.paint("name\tstatus\tcurrent total\ttotal (minus current total)\n")
+                    .to_string(),
+                |a, b| a + &b + "\n",
+            ),
+    );
+}
+
+#[derive(Serialize)]
+struct PunchInDocument<'a> {
+    name: &'a str,
+    started_at: i64,
+    tags: Vec<String>,
+    offline: bool,
+}
+
+#[derive(Serialize)]
+struct PunchOutDocument<'a> {
+    name: &'a str,
+    started_at: i64,
+    finished_at: i64,
+    duration: i64,
+    note: Option<&'a str>,
+    offline: bool,
+}
+
+// the stopped tasks finish exactly when the new one starts
+#[derive(Serialize)]
+struct SwitchDocument<'a> {
+    stopped: Vec<StoppedTaskDocument<'a>>,
+    name: &'a str,
+    started_at: i64,
+    tags: Vec<String>,
+    offline: bool,
+}
+
+#[derive(Serialize)]
+struct StoppedTaskDocument<'a> {
+    name: &'a str,
+    started_at: i64,
+    finished_at: i64,
+    duration: i64,
+}
+
+// a session still in progress counts until now
+#[derive(Serialize)]
+struct SessionDocument<'a> {
+    id: i64,
+    name: &'a str,
+    started_at: i64,
+    finished_at: Option<i64>,
+    duration: i64,
+    note: Option<&'a str>,
+    offline: bool,
+}
+
+#[derive(Serialize)]
+struct SessionListDocument<'a> {
+    sessions: Vec<LoggedSessionDocument<'a>>,
+    offline: bool,
+}
+
+#[derive(Serialize)]
+struct LoggedSessionDocument<'a> {
+    id: i64,
+    name: &'a str,
+    started_at: i64,
+    finished_at: Option<i64>,
+    duration: i64,
+    tags: &'a [String],
+    note: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct ReportDocument<'a> {
+    period: &'a str,
+    group_by: &'a str,
+    since: i64,
+    until: i64,
+    rows: Vec<ReportRowDocument<'a>>,
+    duration: i64,
+    offline: bool,
+}
+
+// a row is either the total of a task or of a day within its period
+#[derive(Serialize)]
+struct ReportRowDocument<'a> {
+    period_start: i64,
+    day: Option<i64>,
+    task: Option<&'a str>,
+    duration: i64,
+}
+
+#[derive(Serialize)]
+struct ImportDocument {
+    imported: usize,
+    skipped: usize,
+    ignored: usize,
+    duration: i64,
+    offline: bool,
+}
+
+#[derive(Serialize)]
+struct TaskPolicyDocument<'a> {
+    task_policy: &'a str,
+    offline: bool,
+}
+
+#[derive(Serialize)]
+pub struct TimerDocument<'a> {
+    pub name: &'a str,
+    pub cycles: usize,
+    pub completed: usize,
+    pub stopped_early: bool,
+    // kept or cancelled when the timer was stopped during an interval
+    pub partial_session: Option<&'static str>,
+    pub offline: bool,
+}
+
+#[derive(Serialize)]
+struct CancelDocument<'a> {
+    name: &'a str,
+    cancelled: bool,
+    offline: bool,
+}
+
+#[derive(Serialize)]
+struct TaskStatDocument<'a> {
+    name: &'a str,
+    in_progress: bool,
+    duration: i64,
+    pomodoros: i64,
+    since: i64,
+    until: i64,
+    offline: bool,
+}
+
+#[derive(Serialize)]
+struct TaskListDocument<'a> {
+    tasks: Vec<TaskDocument<'a>>,
+    offline: bool,
+}
+
+// duration only counts finished sessions, the running one is in
+// current_duration, the same split the text output makes.
+#[derive(Serialize)]
+struct TaskDocument<'a> {
+    name: &'a str,
+    in_progress: bool,
+    started_at: i64,
+    finished_at: Option<i64>,
+    current_duration: Option<i64>,
+    duration: i64,
+    pomodoros: i64,
+    tags: &'a [String],
+}
+
+fn task_list_document(tasks: &[TaskListItem], offline: bool) -> TaskListDocument<'_> {
+    let now_ts = get_ts().unwrap().as_secs() as i64;
+    TaskListDocument {
+        tasks: tasks
+            .iter()
+            .map(|task| TaskDocument {
+                name: &task.name,
+                in_progress: task.finished_at.is_none(),
+                started_at: task.started_at,
+                finished_at: task.finished_at,
+                current_duration: match task.finished_at {
+                    Some(_) => None,
+                    None => Some(now_ts - task.started_at),
+                },
+                duration: task.duration,
+                pomodoros: task.pomodoros,
+                tags: &task.tags,
+            })
+            .collect(),
+        offline,
+    }
+}
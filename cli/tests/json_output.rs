@@ -0,0 +1,86 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Output};
+
+use serde_json::Value;
+
+// a profile of its own keeps the keyring entries of the real accounts out of
+// the way, so every command below stays on the local database
+const PROFILE: &str = "json-output-test";
+
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("punch-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("punch-cli")).unwrap();
+    fs::write(
+        dir.join("punch-cli").join("config.toml"),
+        format!("[profiles.{}]\n", PROFILE),
+    )
+    .unwrap();
+    dir
+}
+
+fn punch(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cli"))
+        .args(["--output", "json", "--profile", PROFILE, "--db"])
+        .arg(dir.join("punch.db"))
+        .args(args)
+        .env("XDG_CONFIG_HOME", dir)
+        .env("XDG_DATA_HOME", dir)
+        .env_remove("PUNCH_PROFILE")
+        .env_remove("PUNCH_TIMEZONE")
+        .output()
+        .unwrap()
+}
+
+// a failed command writes its error document to stderr instead
+fn document(output: &Output) -> Value {
+    let json = if output.status.success() {
+        &output.stdout
+    } else {
+        &output.stderr
+    };
+    serde_json::from_slice(json).unwrap_or_else(|err| {
+        panic!(
+            "not json ({}): {}",
+            err,
+            String::from_utf8_lossy(json).trim()
+        )
+    })
+}
+
+#[test]
+fn add_edit_and_sync_print_json() {
+    let dir = test_dir("add-edit-sync");
+
+    let added = punch(
+        &dir,
+        &[
+            "add",
+            "alpha",
+            "--from",
+            "2024-01-02 09:00",
+            "--to",
+            "2024-01-02 10:00",
+        ],
+    );
+    assert!(added.status.success());
+    let added = document(&added);
+    assert_eq!(added["name"], "alpha");
+    assert_eq!(added["duration"], 3600);
+
+    let session_id = added["id"].to_string();
+    let edited = punch(&dir, &["edit", &session_id, "--end", "2024-01-02 10:30"]);
+    assert!(edited.status.success());
+    let edited = document(&edited);
+    assert_eq!(edited["id"], added["id"]);
+    assert_eq!(edited["duration"], 5400);
+
+    // nothing is logged in under the test profile
+    let synced = punch(&dir, &["sync"]);
+    assert!(!synced.status.success());
+    assert!(synced.stdout.is_empty());
+    assert_eq!(document(&synced)["error"]["code"], "not_logged_in");
+
+    let _ = fs::remove_dir_all(&dir);
+}
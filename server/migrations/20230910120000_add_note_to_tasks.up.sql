@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE "tasks" ADD COLUMN note TEXT;
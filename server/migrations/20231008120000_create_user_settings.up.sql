@@ -0,0 +1,5 @@
+-- Add up migration script here
+CREATE TABLE "user_settings" (
+    user_github_id VARCHAR PRIMARY KEY,
+    task_policy TEXT NOT NULL DEFAULT 'concurrent'
+);
@@ -18,7 +18,11 @@ use sqlx::postgres::PgPoolOptions;
 use crate::api::gh::TokenPayload;
 use crate::routes::{
     auth::{client_id, login, status, verify},
-    punch::{cancel_task, finish_task, get_task, list_tasks, start_new_task},
+    punch::{
+        add_task, cancel_task, finish_task, get_matching_tasks, get_task, import_tasks,
+        list_sessions, list_tasks, report_periods, start_new_task, switch_task, update_session,
+    },
+    settings::{get_settings, update_settings},
 };
 use crate::utils::{configs::fetch_configs, jwt::verify_user_jwt, state::AppDeps};
 
@@ -80,11 +84,24 @@ async fn main() -> std::io::Result<()> {
                     .route("/in", web::post().to(start_new_task))
                     .route("/out", web::post().to(finish_task))
                     .route("/cancel", web::post().to(cancel_task))
+                    .route("/switch", web::post().to(switch_task))
+                    .route("/add", web::post().to(add_task))
+                    .route("/import", web::post().to(import_tasks))
+                    .route("/get", web::get().to(get_matching_tasks))
                     .route("/get/{task_name}", web::get().to(get_task))
                     .route("/list", web::get().to(list_tasks))
+                    .route("/sessions", web::get().to(list_sessions))
+                    .route("/report", web::get().to(report_periods))
+                    .route("/sessions/{session_id}", web::patch().to(update_session))
                     .route("/status", web::get().to(status))
                     .wrap(bearer_middleware.clone()),
             )
+            .service(
+                web::resource("/settings")
+                    .route(web::get().to(get_settings))
+                    .route(web::put().to(update_settings))
+                    .wrap(bearer_middleware.clone()),
+            )
     })
     .bind(("0.0.0.0", 4000))?
     .run()
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+pub const TASK_POLICY_CONCURRENT: &str = "concurrent";
+pub const TASK_POLICY_SINGLE: &str = "single";
+pub const TASK_POLICY_AUTO_SWITCH: &str = "auto-switch";
+
+#[derive(Deserialize, FromRow, Serialize)]
+pub struct UserSettingsModel {
+    pub task_policy: String,
+}
+
+impl UserSettingsModel {
+    pub fn is_valid_task_policy(policy: &str) -> bool {
+        matches!(
+            policy,
+            TASK_POLICY_CONCURRENT | TASK_POLICY_SINGLE | TASK_POLICY_AUTO_SWITCH
+        )
+    }
+}
@@ -8,6 +8,8 @@ pub struct TaskListModel {
     pub duration: BigDecimal,
     pub started_at: i64,
     pub finished_at: Option<i64>,
+    pub pomodoros: i64,
+    pub tags: Vec<String>,
 }
 
 #[derive(Deserialize, FromRow, Serialize)]
@@ -16,6 +18,15 @@ pub struct TaskListModelForResponse {
     pub duration: i64,
     pub started_at: i64,
     pub finished_at: Option<i64>,
+    pub tags: Vec<String>,
+    pub pomodoros: i64,
+}
+
+#[derive(Deserialize, FromRow, Serialize)]
+pub struct PeriodReportRowModel {
+    pub period_start: i64,
+    pub name: String,
+    pub duration: i64,
 }
 
 #[derive(Deserialize, FromRow, Serialize)]
@@ -25,6 +36,10 @@ pub struct TaskModel {
     pub user_github_id: String,
     pub started_at: i64,
     pub finished_at: Option<i64>,
+    pub note: Option<String>,
+    pub pomodoro: bool,
+    #[sqlx(default)]
+    pub tags: Vec<String>,
 }
 
 impl TaskModel {
@@ -34,6 +49,9 @@ impl TaskModel {
             "name": self.name.to_owned(),
             "started_at": self.started_at.to_owned(),
             "finished_at": self.finished_at.to_owned(),
+            "note": self.note.to_owned(),
+            "pomodoro": self.pomodoro,
+            "tags": self.tags.to_owned(),
         });
     }
 }
@@ -41,7 +59,11 @@ impl TaskModel {
 pub fn tasks_to_task_report(tasks: &Vec<TaskModel>, name: &str, right_now: i64) -> impl Serialize {
     let mut is_in_progress = false;
     let mut duration_sum: i64 = 0;
+    let mut pomodoros: i64 = 0;
     for task in tasks {
+        if task.pomodoro {
+            pomodoros += 1;
+        }
         if task.finished_at.is_none() {
             is_in_progress = true;
             duration_sum = duration_sum + (right_now - task.started_at)
@@ -53,5 +75,6 @@ pub fn tasks_to_task_report(tasks: &Vec<TaskModel>, name: &str, right_now: i64)
         "name": name.to_owned(),
         "status": if is_in_progress { "in progress" } else { "complete "},
         "duration": duration_sum,
+        "pomodoros": pomodoros,
     });
 }
@@ -3,24 +3,158 @@ use std::time::SystemTime;
 use actix_web::{web, HttpResponse, Responder};
 use bigdecimal::ToPrimitive;
 use serde::Deserialize;
+use sqlx::PgConnection;
 
 use crate::api::gh::TokenPayload;
+use crate::models::settings::{TASK_POLICY_AUTO_SWITCH, TASK_POLICY_SINGLE};
 use crate::models::tasks::{
-    tasks_to_task_report, TaskListModel, TaskListModelForResponse, TaskModel,
+    tasks_to_task_report, PeriodReportRowModel, TaskListModel, TaskListModelForResponse, TaskModel,
 };
 use crate::utils::{errors::PunchTaskError, state::AppDeps};
 
 use super::auth::TasksCount;
+use super::settings::fetch_user_settings;
+
+const DEFAULT_SESSION_PAGE_SIZE: i64 = 100;
+const MAX_SESSION_PAGE_SIZE: i64 = 500;
 
 #[derive(Deserialize)]
 pub struct BaseTaskInfo {
     name: String,
+    timestamp: Option<i64>,
+    #[serde(default)]
+    tags: Vec<String>,
+    note: Option<String>,
+    // set when a timer ran for its whole interval
+    #[serde(default)]
+    pomodoro: bool,
+}
+
+impl BaseTaskInfo {
+    // punches replayed by offline clients carry the time they were recorded at,
+    // which must never be later than the current server time.
+    fn punched_at(&self) -> Result<i64, PunchTaskError> {
+        let right_now = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(n) => n.as_secs() as i64,
+            Err(_) => return Err(PunchTaskError::InternalError),
+        };
+        Ok(match self.timestamp {
+            Some(ts) => std::cmp::min(ts, right_now),
+            None => right_now,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ManualTaskInfo {
+    name: String,
+    started_at: i64,
+    finished_at: i64,
+    #[serde(default)]
+    tags: Vec<String>,
+    note: Option<String>,
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SessionUpdateInfo {
+    started_at: Option<i64>,
+    finished_at: Option<i64>,
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ImportedTaskInfo {
+    name: String,
+    started_at: i64,
+    finished_at: Option<i64>,
+    #[serde(default)]
+    tags: Vec<String>,
+    note: Option<String>,
+    #[serde(default)]
+    pomodoro: bool,
+}
+
+#[derive(Deserialize)]
+pub struct TaskImportInfo {
+    tasks: Vec<ImportedTaskInfo>,
 }
 
 #[derive(Deserialize)]
 pub struct TimeFilterInfo {
     pub since: Option<i64>,
     pub until: Option<i64>,
+    pub tags: Option<String>,
+    pub prefix: Option<String>,
+}
+
+impl TimeFilterInfo {
+    fn task_prefix(&self) -> Option<String> {
+        self.prefix
+            .as_ref()
+            .map(|prefix| prefix.trim_end_matches('/').to_lowercase())
+            .filter(|prefix| !prefix.is_empty())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SessionFilterInfo {
+    pub name: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub order: Option<String>,
+}
+
+impl SessionFilterInfo {
+    fn page_size(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_SESSION_PAGE_SIZE)
+            .clamp(1, MAX_SESSION_PAGE_SIZE)
+    }
+
+    fn oldest_first(&self) -> bool {
+        self.order.as_deref() == Some("asc")
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PeriodReportInfo {
+    pub period: String,
+    pub since: i64,
+    pub until: Option<i64>,
+    pub tz: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TagFilterInfo {
+    pub tags: Option<String>,
+}
+
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim().trim_start_matches('+').to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    normalized.sort();
+    normalized.dedup();
+    normalized
+}
+
+fn parse_tag_filter(tags: &Option<String>) -> Vec<String> {
+    match tags {
+        Some(tags) => normalize_tags(
+            &tags
+                .split(',')
+                .map(|tag| tag.to_owned())
+                .collect::<Vec<String>>(),
+        ),
+        None => vec![],
+    }
 }
 
 pub async fn start_new_task(
@@ -52,6 +186,49 @@ pub async fn start_new_task(
     if dupe_count > 0 {
         return Err(PunchTaskError::TaskAlreadyInProgress);
     }
+    let punched_at = task_info.punched_at()?;
+    let tags = normalize_tags(&task_info.tags);
+    let mut tx = match app_deps.db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    let settings = match fetch_user_settings(&mut tx, &token.user.id.to_string()).await {
+        Ok(settings) => settings,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    let stopped = match settings.task_policy.as_str() {
+        TASK_POLICY_SINGLE => {
+            let running_count = match sqlx::query_as::<_, TasksCount>(
+                "
+                    SELECT
+                        COUNT(*)
+                    FROM
+                        tasks
+                    WHERE
+                        user_github_id = $1 AND
+                        finished_at IS NULL;
+                ",
+            )
+            .bind(token.user.id.to_string())
+            .fetch_one(&mut *tx)
+            .await
+            {
+                Ok(count) => count.count,
+                Err(_) => return Err(PunchTaskError::InternalError),
+            };
+            if running_count > 0 {
+                return Err(PunchTaskError::AnotherTaskInProgress);
+            }
+            vec![]
+        }
+        TASK_POLICY_AUTO_SWITCH => {
+            match finish_running_tasks(&mut tx, &token.user.id.to_string(), punched_at).await {
+                Ok(stopped) => stopped,
+                Err(_) => return Err(PunchTaskError::InternalError),
+            }
+        }
+        _ => vec![],
+    };
     let new_task_op = sqlx::query_as::<_, TaskModel>(
         "
             INSERT INTO tasks (name, user_github_id, started_at)
@@ -61,22 +238,36 @@ pub async fn start_new_task(
     )
     .bind(&task_name)
     .bind(token.user.id.to_string())
-    .bind(
-        match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-            Ok(n) => n.as_secs() as i64,
-            Err(_) => return Err(PunchTaskError::InternalError),
-        },
-    )
-    .fetch_one(&app_deps.db_pool)
+    .bind(punched_at)
+    .fetch_one(&mut *tx)
     .await;
-    match new_task_op {
-        Ok(new_task) => {
-            return Ok(HttpResponse::Ok().json(new_task.to_json()));
-        }
+    let mut new_task = match new_task_op {
+        Ok(new_task) => new_task,
         Err(_) => {
             return Err(PunchTaskError::InternalError);
         }
+    };
+    let tags_op = sqlx::query(
+        "
+            INSERT INTO task_tags (task_id, tag)
+            SELECT $1, UNNEST($2::TEXT[]);
+        ",
+    )
+    .bind(new_task.id)
+    .bind(&tags)
+    .execute(&mut *tx)
+    .await;
+    if tags_op.is_err() || tx.commit().await.is_err() {
+        return Err(PunchTaskError::InternalError);
     }
+    new_task.tags = tags;
+    // tasks closed by the auto-switch policy are listed so clients can mirror them
+    let mut response = serde_json::json!(new_task.to_json());
+    response["stopped"] = serde_json::json!(stopped
+        .iter()
+        .map(|task| task.to_json())
+        .collect::<Vec<_>>());
+    return Ok(HttpResponse::Ok().json(response));
 }
 
 pub async fn finish_task(
@@ -85,26 +276,35 @@ pub async fn finish_task(
     task_info: web::Json<BaseTaskInfo>,
 ) -> impl Responder {
     let task_name = task_info.name.to_lowercase();
-    let finished_at = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-        Ok(n) => n.as_secs() as i64,
-        Err(_) => return Err(PunchTaskError::InternalError),
-    };
+    let finished_at = task_info.punched_at()?;
     let update_op = sqlx::query_as::<_, TaskModel>(
         "
-            UPDATE 
-                tasks
-            SET
-                finished_at = $1
-            WHERE
-                name = $2 AND
-                user_github_id = $3 AND
-                finished_at IS NULL
-            RETURNING *;
+            WITH updated AS (
+                UPDATE 
+                    tasks
+                SET
+                    finished_at = $1,
+                    note = $4,
+                    pomodoro = $5
+                WHERE
+                    name = $2 AND
+                    user_github_id = $3 AND
+                    started_at <= $1 AND
+                    finished_at IS NULL
+                RETURNING *
+            )
+            SELECT
+                updated.*,
+                ARRAY(SELECT tag FROM task_tags WHERE task_id = updated.id ORDER BY tag) AS tags
+            FROM
+                updated;
         ",
     )
     .bind(finished_at)
     .bind(&task_name)
     .bind(token.user.id.to_string())
+    .bind(&task_info.note)
+    .bind(task_info.pomodoro)
     .fetch_one(&app_deps.db_pool)
     .await;
     match update_op {
@@ -150,13 +350,346 @@ pub async fn cancel_task(
     }
 }
 
+async fn finish_running_tasks(
+    conn: &mut PgConnection,
+    user_github_id: &str,
+    finished_at: i64,
+) -> Result<Vec<TaskModel>, sqlx::Error> {
+    sqlx::query_as::<_, TaskModel>(
+        "
+            WITH updated AS (
+                UPDATE
+                    tasks
+                SET
+                    finished_at = GREATEST(started_at, $1)
+                WHERE
+                    user_github_id = $2 AND
+                    finished_at IS NULL
+                RETURNING *
+            )
+            SELECT
+                updated.*,
+                ARRAY(SELECT tag FROM task_tags WHERE task_id = updated.id ORDER BY tag) AS tags
+            FROM
+                updated
+            ORDER BY
+                updated.name;
+        ",
+    )
+    .bind(finished_at)
+    .bind(user_github_id)
+    .fetch_all(conn)
+    .await
+}
+
+// every running task is finished at the exact moment the new one starts, so
+// the switch never leaves a gap or an overlap behind.
+pub async fn switch_task(
+    app_deps: web::Data<AppDeps>,
+    token: web::ReqData<TokenPayload>,
+    task_info: web::Json<BaseTaskInfo>,
+) -> impl Responder {
+    let task_name = task_info.name.to_lowercase();
+    let punched_at = task_info.punched_at()?;
+    let tags = normalize_tags(&task_info.tags);
+    let mut tx = match app_deps.db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    let dupe_count = match sqlx::query_as::<_, TasksCount>(
+        "
+            SELECT
+                COUNT(*)
+            FROM
+                tasks
+            WHERE
+                name = $1 AND
+                user_github_id = $2 AND
+                finished_at IS NULL;
+        ",
+    )
+    .bind(&task_name)
+    .bind(token.user.id.to_string())
+    .fetch_one(&mut *tx)
+    .await
+    {
+        Ok(count) => count.count,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    if dupe_count > 0 {
+        return Err(PunchTaskError::TaskAlreadyInProgress);
+    }
+    let stop_op = finish_running_tasks(&mut tx, &token.user.id.to_string(), punched_at).await;
+    let stopped = match stop_op {
+        Ok(stopped) => stopped,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    let new_task_op = sqlx::query_as::<_, TaskModel>(
+        "
+            INSERT INTO tasks (name, user_github_id, started_at)
+            VALUES ($1, $2, $3)
+            RETURNING *;
+        ",
+    )
+    .bind(&task_name)
+    .bind(token.user.id.to_string())
+    .bind(punched_at)
+    .fetch_one(&mut *tx)
+    .await;
+    let mut new_task = match new_task_op {
+        Ok(new_task) => new_task,
+        Err(_) => {
+            return Err(PunchTaskError::InternalError);
+        }
+    };
+    let tags_op = sqlx::query(
+        "
+            INSERT INTO task_tags (task_id, tag)
+            SELECT $1, UNNEST($2::TEXT[]);
+        ",
+    )
+    .bind(new_task.id)
+    .bind(&tags)
+    .execute(&mut *tx)
+    .await;
+    if tags_op.is_err() || tx.commit().await.is_err() {
+        return Err(PunchTaskError::InternalError);
+    }
+    new_task.tags = tags;
+    let stopped: Vec<_> = stopped.iter().map(|task| task.to_json()).collect();
+    return Ok(HttpResponse::Ok().json(serde_json::json!({
+        "stopped": stopped,
+        "started": new_task.to_json(),
+    })));
+}
+
+pub async fn add_task(
+    app_deps: web::Data<AppDeps>,
+    token: web::ReqData<TokenPayload>,
+    task_info: web::Json<ManualTaskInfo>,
+) -> impl Responder {
+    let task_name = task_info.name.to_lowercase();
+    let right_now = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs() as i64,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    if task_info.finished_at <= task_info.started_at {
+        return Err(PunchTaskError::InvalidTimeRange);
+    }
+    if task_info.finished_at > right_now {
+        return Err(PunchTaskError::FutureSession);
+    }
+    let tags = normalize_tags(&task_info.tags);
+    let mut tx = match app_deps.db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    if !task_info.force {
+        // a session still in progress is treated as running until now
+        let overlap_count = match sqlx::query_as::<_, TasksCount>(
+            "
+                SELECT
+                    COUNT(*)
+                FROM
+                    tasks
+                WHERE
+                    name = $1 AND
+                    user_github_id = $2 AND
+                    started_at < $4 AND
+                    (finished_at IS NULL OR finished_at > $3);
+            ",
+        )
+        .bind(&task_name)
+        .bind(token.user.id.to_string())
+        .bind(task_info.started_at)
+        .bind(task_info.finished_at)
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(count) => count.count,
+            Err(_) => return Err(PunchTaskError::InternalError),
+        };
+        if overlap_count > 0 {
+            return Err(PunchTaskError::OverlappingSession);
+        }
+    }
+    let new_task_op = sqlx::query_as::<_, TaskModel>(
+        "
+            INSERT INTO tasks (name, user_github_id, started_at, finished_at, note)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *;
+        ",
+    )
+    .bind(&task_name)
+    .bind(token.user.id.to_string())
+    .bind(task_info.started_at)
+    .bind(task_info.finished_at)
+    .bind(&task_info.note)
+    .fetch_one(&mut *tx)
+    .await;
+    let mut new_task = match new_task_op {
+        Ok(new_task) => new_task,
+        Err(_) => {
+            return Err(PunchTaskError::InternalError);
+        }
+    };
+    let tags_op = sqlx::query(
+        "
+            INSERT INTO task_tags (task_id, tag)
+            SELECT $1, UNNEST($2::TEXT[]);
+        ",
+    )
+    .bind(new_task.id)
+    .bind(&tags)
+    .execute(&mut *tx)
+    .await;
+    if tags_op.is_err() || tx.commit().await.is_err() {
+        return Err(PunchTaskError::InternalError);
+    }
+    new_task.tags = tags;
+    return Ok(HttpResponse::Ok().json(new_task.to_json()));
+}
+
+pub async fn update_session(
+    app_deps: web::Data<AppDeps>,
+    token: web::ReqData<TokenPayload>,
+    session_id: web::Path<i64>,
+    update_info: web::Json<SessionUpdateInfo>,
+) -> impl Responder {
+    let right_now = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs() as i64,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    let mut tx = match app_deps.db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    let session_op = sqlx::query_as::<_, TaskModel>(
+        "
+            SELECT
+                *
+            FROM
+                tasks
+            WHERE
+                id = $1 AND
+                user_github_id = $2
+            FOR UPDATE;
+        ",
+    )
+    .bind(*session_id)
+    .bind(token.user.id.to_string())
+    .fetch_one(&mut *tx)
+    .await;
+    let session = match session_op {
+        Ok(session) => session,
+        Err(sqlx::Error::RowNotFound) => return Err(PunchTaskError::SessionNotFound),
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    let started_at = update_info.started_at.unwrap_or(session.started_at);
+    let finished_at = update_info.finished_at.or(session.finished_at);
+    if matches!(finished_at, Some(ts) if ts <= started_at) {
+        return Err(PunchTaskError::InvalidTimeRange);
+    }
+    if finished_at.unwrap_or(started_at) > right_now {
+        return Err(PunchTaskError::FutureSession);
+    }
+    if !update_info.force {
+        let overlap_count = match sqlx::query_as::<_, TasksCount>(
+            "
+                SELECT
+                    COUNT(*)
+                FROM
+                    tasks
+                WHERE
+                    name = $1 AND
+                    user_github_id = $2 AND
+                    id <> $3 AND
+                    started_at < $5 AND
+                    (finished_at IS NULL OR finished_at > $4);
+            ",
+        )
+        .bind(&session.name)
+        .bind(token.user.id.to_string())
+        .bind(session.id)
+        .bind(started_at)
+        .bind(finished_at.unwrap_or(right_now))
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(count) => count.count,
+            Err(_) => return Err(PunchTaskError::InternalError),
+        };
+        if overlap_count > 0 {
+            return Err(PunchTaskError::OverlappingSession);
+        }
+    }
+    let update_op = sqlx::query_as::<_, TaskModel>(
+        "
+            WITH updated AS (
+                UPDATE
+                    tasks
+                SET
+                    started_at = $1,
+                    finished_at = $2
+                WHERE
+                    id = $3
+                RETURNING *
+            )
+            SELECT
+                updated.*,
+                ARRAY(SELECT tag FROM task_tags WHERE task_id = updated.id ORDER BY tag) AS tags
+            FROM
+                updated;
+        ",
+    )
+    .bind(started_at)
+    .bind(finished_at)
+    .bind(session.id)
+    .fetch_one(&mut *tx)
+    .await;
+    match update_op {
+        Ok(updated_session) => {
+            if tx.commit().await.is_err() {
+                return Err(PunchTaskError::InternalError);
+            }
+            // clients find their copy of the session by the times it had
+            let mut response = serde_json::json!(updated_session.to_json());
+            response["previous_started_at"] = serde_json::json!(session.started_at);
+            response["previous_finished_at"] = serde_json::json!(session.finished_at);
+            return Ok(HttpResponse::Ok().json(response));
+        }
+        Err(_) => {
+            return Err(PunchTaskError::InternalError);
+        }
+    }
+}
+
 pub async fn get_task(
     app_deps: web::Data<AppDeps>,
     token: web::ReqData<TokenPayload>,
     name: web::Path<String>,
     ts_filter: web::Query<TimeFilterInfo>,
 ) -> impl Responder {
-    let task_name = name.to_lowercase();
+    report_tasks(&app_deps, &token, Some(name.to_lowercase()), &ts_filter).await
+}
+
+pub async fn get_matching_tasks(
+    app_deps: web::Data<AppDeps>,
+    token: web::ReqData<TokenPayload>,
+    ts_filter: web::Query<TimeFilterInfo>,
+) -> impl Responder {
+    if parse_tag_filter(&ts_filter.tags).is_empty() && ts_filter.task_prefix().is_none() {
+        return Err(PunchTaskError::MissingTaskFilter);
+    }
+    report_tasks(&app_deps, &token, None, &ts_filter).await
+}
+
+async fn report_tasks(
+    app_deps: &AppDeps,
+    token: &TokenPayload,
+    task_name: Option<String>,
+    ts_filter: &TimeFilterInfo,
+) -> Result<HttpResponse, PunchTaskError> {
     let right_now = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
         Ok(n) => n.as_secs() as i64,
         Err(_) => return Err(PunchTaskError::InternalError),
@@ -169,16 +702,24 @@ pub async fn get_task(
         Some(until) => until,
         None => std::i64::MAX,
     };
+    let tags = parse_tag_filter(&ts_filter.tags);
+    let prefix = ts_filter.task_prefix();
     let get_task_op = sqlx::query_as::<_, TaskModel>(
         "
-            SELECT *
+            SELECT
+                tasks.*,
+                ARRAY(SELECT tag FROM task_tags WHERE task_id = tasks.id ORDER BY tag) AS tags
             FROM
                 tasks
             WHERE 
-                name = $1 AND 
+                ($1::TEXT IS NULL OR name = $1) AND 
+                ($6::TEXT IS NULL OR name = $6 OR STARTS_WITH(name, $6 || '/')) AND
                 user_github_id = $2 AND
                 started_at <= $3 AND
-                (finished_at IS NULL OR finished_at >= $4)
+                (finished_at IS NULL OR finished_at >= $4) AND
+                (
+                    SELECT COUNT(*) FROM task_tags WHERE task_id = tasks.id AND tag = ANY($5)
+                ) = CARDINALITY($5::TEXT[])
             ORDER BY
                 started_at ASC;
         ",
@@ -187,6 +728,8 @@ pub async fn get_task(
     .bind(token.user.id.to_string())
     .bind(until)
     .bind(since)
+    .bind(&tags)
+    .bind(&prefix)
     .fetch_all(&app_deps.db_pool)
     .await;
     match get_task_op {
@@ -194,7 +737,16 @@ pub async fn get_task(
             if tasks.len() < 1 {
                 return Err(PunchTaskError::TaskNotFound);
             }
-            let task_report = tasks_to_task_report(&tasks, &task_name, right_now);
+            let report_name = match (task_name, prefix) {
+                (Some(task_name), _) => task_name,
+                (None, Some(prefix)) => format!("{}/", prefix),
+                (None, None) => tags
+                    .iter()
+                    .map(|tag| format!("+{}", tag))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+            };
+            let task_report = tasks_to_task_report(&tasks, &report_name, right_now);
             return Ok(HttpResponse::Ok().json(task_report));
         }
         Err(_) => {
@@ -206,6 +758,7 @@ pub async fn get_task(
 pub async fn list_tasks(
     app_deps: web::Data<AppDeps>,
     token: web::ReqData<TokenPayload>,
+    tag_filter: web::Query<TagFilterInfo>,
 ) -> impl Responder {
     let task_rows = sqlx::query_as::<_, TaskListModel>(
         r#"
@@ -213,16 +766,22 @@ pub async fn list_tasks(
                 name,
                 MAX(started_at) as started_at,
                 CASE WHEN count(*) - count(finished_at) > 0 THEN NULL ELSE MAX(finished_at) END as finished_at,
-                SUM(finished_at - started_at) as duration
+                COALESCE(SUM(finished_at - started_at), 0) as duration,
+                COUNT(*) FILTER (WHERE pomodoro) as pomodoros,
+                ARRAY(SELECT DISTINCT tag FROM task_tags WHERE task_id = ANY(ARRAY_AGG(tasks.id)) ORDER BY tag) as tags
             FROM
                 tasks
             WHERE
-                user_github_id = $1
+                user_github_id = $1 AND
+                (
+                    SELECT COUNT(*) FROM task_tags WHERE task_id = tasks.id AND tag = ANY($2)
+                ) = CARDINALITY($2::TEXT[])
             GROUP BY
                 name;
             "#
     )
     .bind(token.user.id.to_string())
+    .bind(parse_tag_filter(&tag_filter.tags))
     .fetch_all(&app_deps.db_pool)
     .await;
     match task_rows {
@@ -234,6 +793,8 @@ pub async fn list_tasks(
                     duration: task_row.duration.to_i64().unwrap_or(0),
                     started_at: task_row.started_at,
                     finished_at: task_row.finished_at,
+                    tags: task_row.tags.to_owned(),
+                    pomodoros: task_row.pomodoros,
                 })
                 .collect();
             return Ok(HttpResponse::Ok().json(serde_json::json!(tasks)));
@@ -243,3 +804,271 @@ pub async fn list_tasks(
         }
     }
 }
+
+pub async fn import_tasks(
+    app_deps: web::Data<AppDeps>,
+    token: web::ReqData<TokenPayload>,
+    import_info: web::Json<TaskImportInfo>,
+) -> impl Responder {
+    let names: Vec<String> = import_info
+        .tasks
+        .iter()
+        .map(|task| task.name.to_lowercase())
+        .collect();
+    let started: Vec<i64> = import_info
+        .tasks
+        .iter()
+        .map(|task| task.started_at)
+        .collect();
+    let finished: Vec<Option<i64>> = import_info
+        .tasks
+        .iter()
+        .map(|task| task.finished_at)
+        .collect();
+    let notes: Vec<Option<String>> = import_info
+        .tasks
+        .iter()
+        .map(|task| task.note.to_owned())
+        .collect();
+    let pomodoros: Vec<bool> = import_info
+        .tasks
+        .iter()
+        .map(|task| task.pomodoro)
+        .collect();
+    let mut tx = match app_deps.db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    // a session sent twice in the batch is imported once, as it was first sent
+    let import_op = sqlx::query_as::<_, TaskModel>(
+        "
+            INSERT INTO tasks (name, user_github_id, started_at, finished_at, note, pomodoro)
+            SELECT
+                imported.name, $1, imported.started_at, imported.finished_at, imported.note,
+                imported.pomodoro
+            FROM
+                (
+                    SELECT DISTINCT ON (name, started_at) *
+                    FROM
+                        UNNEST($2::TEXT[], $3::BIGINT[], $4::BIGINT[], $5::TEXT[], $6::BOOLEAN[])
+                            WITH ORDINALITY
+                            AS imported(name, started_at, finished_at, note, pomodoro, position)
+                    ORDER BY name, started_at, position
+                ) AS imported
+            WHERE
+                (imported.finished_at IS NULL OR imported.finished_at >= imported.started_at) AND
+                NOT EXISTS (
+                    SELECT 1
+                    FROM
+                        tasks
+                    WHERE
+                        tasks.user_github_id = $1 AND
+                        tasks.name = imported.name AND
+                        (
+                            tasks.started_at = imported.started_at OR
+                            (tasks.finished_at IS NULL AND imported.finished_at IS NULL)
+                        )
+                )
+            RETURNING *;
+        ",
+    )
+    .bind(token.user.id.to_string())
+    .bind(&names)
+    .bind(&started)
+    .bind(&finished)
+    .bind(&notes)
+    .bind(&pomodoros)
+    .fetch_all(&mut *tx)
+    .await;
+    let imported = match import_op {
+        Ok(imported) => imported,
+        Err(_) => {
+            return Err(PunchTaskError::InternalError);
+        }
+    };
+    let mut tagged_ids: Vec<i64> = vec![];
+    let mut tags: Vec<String> = vec![];
+    for task in imported.iter() {
+        let task_info = import_info.tasks.iter().find(|task_info| {
+            task_info.name.to_lowercase() == task.name && task_info.started_at == task.started_at
+        });
+        if let Some(task_info) = task_info {
+            for tag in normalize_tags(&task_info.tags) {
+                tagged_ids.push(task.id);
+                tags.push(tag);
+            }
+        }
+    }
+    let tags_op = sqlx::query(
+        "
+            INSERT INTO task_tags (task_id, tag)
+            SELECT * FROM UNNEST($1::BIGINT[], $2::TEXT[]);
+        ",
+    )
+    .bind(&tagged_ids)
+    .bind(&tags)
+    .execute(&mut *tx)
+    .await;
+    if tags_op.is_err() || tx.commit().await.is_err() {
+        return Err(PunchTaskError::InternalError);
+    }
+    let duration: i64 = imported
+        .iter()
+        .filter_map(|task| task.finished_at.map(|ts| ts - task.started_at))
+        .sum();
+    return Ok(HttpResponse::Ok().json(serde_json::json!({
+        "imported": imported.len(),
+        "skipped": import_info.tasks.len() - imported.len(),
+        "duration": duration,
+    })));
+}
+
+pub async fn list_sessions(
+    app_deps: web::Data<AppDeps>,
+    token: web::ReqData<TokenPayload>,
+    session_filter: web::Query<SessionFilterInfo>,
+) -> impl Responder {
+    let task_name = session_filter.name.as_ref().map(|name| name.to_lowercase());
+    let sessions_op = sqlx::query_as::<_, TaskModel>(
+        "
+            SELECT
+                tasks.*,
+                ARRAY(SELECT tag FROM task_tags WHERE task_id = tasks.id ORDER BY tag) AS tags
+            FROM
+                tasks
+            WHERE
+                user_github_id = $1 AND
+                ($2::TEXT IS NULL OR name = $2) AND
+                ($3::BIGINT IS NULL OR finished_at IS NULL OR finished_at >= $3) AND
+                ($4::BIGINT IS NULL OR started_at <= $4)
+            ORDER BY
+                CASE WHEN $7 THEN started_at END ASC,
+                CASE WHEN $7 THEN id END ASC,
+                started_at DESC,
+                id DESC
+            LIMIT $5
+            OFFSET $6;
+        ",
+    )
+    .bind(token.user.id.to_string())
+    .bind(&task_name)
+    .bind(session_filter.since)
+    .bind(session_filter.until)
+    .bind(session_filter.page_size())
+    .bind(std::cmp::max(session_filter.offset.unwrap_or(0), 0))
+    .bind(session_filter.oldest_first())
+    .fetch_all(&app_deps.db_pool)
+    .await;
+    match sessions_op {
+        Ok(sessions) => {
+            let sessions: Vec<_> = sessions.iter().map(|session| session.to_json()).collect();
+            return Ok(HttpResponse::Ok().json(sessions));
+        }
+        Err(_) => {
+            return Err(PunchTaskError::InternalError);
+        }
+    }
+}
+
+pub async fn report_periods(
+    app_deps: web::Data<AppDeps>,
+    token: web::ReqData<TokenPayload>,
+    report_info: web::Query<PeriodReportInfo>,
+) -> impl Responder {
+    let right_now = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs() as i64,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    if !matches!(report_info.period.as_str(), "day" | "week" | "month") {
+        return Err(PunchTaskError::InvalidReportPeriod);
+    }
+    let time_zone = report_info
+        .tz
+        .to_owned()
+        .unwrap_or_else(|| String::from("UTC"));
+    let tz_count = match sqlx::query_as::<_, TasksCount>(
+        "
+            SELECT COUNT(*) FROM pg_timezone_names WHERE name = $1;
+        ",
+    )
+    .bind(&time_zone)
+    .fetch_one(&app_deps.db_pool)
+    .await
+    {
+        Ok(count) => count.count,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    if tz_count < 1 {
+        return Err(PunchTaskError::UnknownTimeZone);
+    }
+    let until = std::cmp::min(report_info.until.unwrap_or(right_now), right_now);
+    // periods are cut at local midnight in the requested time zone, sessions
+    // running across a cut count towards both sides of it.
+    let report_op = sqlx::query_as::<_, PeriodReportRowModel>(
+        "
+            WITH sessions AS (
+                SELECT
+                    name,
+                    started_at,
+                    COALESCE(finished_at, $5) AS finished_at
+                FROM
+                    tasks
+                WHERE
+                    user_github_id = $1 AND
+                    started_at < $4 AND
+                    COALESCE(finished_at, $5) > $3
+            ),
+            periods AS (
+                SELECT
+                    EXTRACT(EPOCH FROM (period AT TIME ZONE $6))::BIGINT AS period_start,
+                    EXTRACT(EPOCH FROM ((period + ('1 ' || $2)::INTERVAL) AT TIME ZONE $6))::BIGINT
+                        AS period_end
+                FROM
+                    GENERATE_SERIES(
+                        DATE_TRUNC($2, TO_TIMESTAMP($3) AT TIME ZONE $6),
+                        TO_TIMESTAMP($4) AT TIME ZONE $6,
+                        ('1 ' || $2)::INTERVAL
+                    ) AS period
+            )
+            SELECT
+                periods.period_start,
+                sessions.name,
+                SUM(
+                    LEAST(sessions.finished_at, periods.period_end, $4) -
+                    GREATEST(sessions.started_at, periods.period_start, $3)
+                )::BIGINT AS duration
+            FROM
+                periods
+                JOIN sessions ON
+                    sessions.started_at < periods.period_end AND
+                    sessions.finished_at > periods.period_start
+            GROUP BY
+                periods.period_start,
+                sessions.name
+            HAVING
+                SUM(
+                    LEAST(sessions.finished_at, periods.period_end, $4) -
+                    GREATEST(sessions.started_at, periods.period_start, $3)
+                ) > 0
+            ORDER BY
+                periods.period_start,
+                sessions.name;
+        ",
+    )
+    .bind(token.user.id.to_string())
+    .bind(&report_info.period)
+    .bind(report_info.since)
+    .bind(until)
+    .bind(right_now)
+    .bind(&time_zone)
+    .fetch_all(&app_deps.db_pool)
+    .await;
+    match report_op {
+        Ok(rows) => {
+            return Ok(HttpResponse::Ok().json(rows));
+        }
+        Err(_) => {
+            return Err(PunchTaskError::InternalError);
+        }
+    }
+}
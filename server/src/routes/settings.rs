@@ -0,0 +1,69 @@
+use actix_web::{web, HttpResponse, Responder};
+use sqlx::PgConnection;
+
+use crate::api::gh::TokenPayload;
+use crate::models::settings::{UserSettingsModel, TASK_POLICY_CONCURRENT};
+use crate::utils::{errors::PunchTaskError, state::AppDeps};
+
+// users who never changed their settings get the defaults
+pub async fn fetch_user_settings(
+    conn: &mut PgConnection,
+    user_github_id: &str,
+) -> Result<UserSettingsModel, sqlx::Error> {
+    let settings = sqlx::query_as::<_, UserSettingsModel>(
+        "
+            SELECT
+                task_policy
+            FROM
+                user_settings
+            WHERE
+                user_github_id = $1;
+        ",
+    )
+    .bind(user_github_id)
+    .fetch_optional(conn)
+    .await?;
+    Ok(settings.unwrap_or(UserSettingsModel {
+        task_policy: TASK_POLICY_CONCURRENT.to_owned(),
+    }))
+}
+
+pub async fn get_settings(
+    app_deps: web::Data<AppDeps>,
+    token: web::ReqData<TokenPayload>,
+) -> impl Responder {
+    let mut conn = match app_deps.db_pool.acquire().await {
+        Ok(conn) => conn,
+        Err(_) => return Err(PunchTaskError::InternalError),
+    };
+    match fetch_user_settings(&mut conn, &token.user.id.to_string()).await {
+        Ok(settings) => Ok(HttpResponse::Ok().json(settings)),
+        Err(_) => Err(PunchTaskError::InternalError),
+    }
+}
+
+pub async fn update_settings(
+    app_deps: web::Data<AppDeps>,
+    token: web::ReqData<TokenPayload>,
+    settings_info: web::Json<UserSettingsModel>,
+) -> impl Responder {
+    if !UserSettingsModel::is_valid_task_policy(&settings_info.task_policy) {
+        return Err(PunchTaskError::InvalidTaskPolicy);
+    }
+    let update_op = sqlx::query_as::<_, UserSettingsModel>(
+        "
+            INSERT INTO user_settings (user_github_id, task_policy)
+            VALUES ($1, $2)
+            ON CONFLICT (user_github_id) DO UPDATE SET task_policy = EXCLUDED.task_policy
+            RETURNING task_policy;
+        ",
+    )
+    .bind(token.user.id.to_string())
+    .bind(&settings_info.task_policy)
+    .fetch_one(&app_deps.db_pool)
+    .await;
+    match update_op {
+        Ok(settings) => Ok(HttpResponse::Ok().json(settings)),
+        Err(_) => Err(PunchTaskError::InternalError),
+    }
+}
@@ -27,6 +27,33 @@ pub enum PunchTaskError {
 
     #[display(fmt = "no such task in progress found")]
     InProgressTaskNotFound,
+
+    #[display(fmt = "either a task name or a tag must be given")]
+    MissingTaskFilter,
+
+    #[display(fmt = "the session must finish after it starts")]
+    InvalidTimeRange,
+
+    #[display(fmt = "the session cannot finish in the future")]
+    FutureSession,
+
+    #[display(fmt = "the session overlaps another session of the same task")]
+    OverlappingSession,
+
+    #[display(fmt = "no session with the given id found")]
+    SessionNotFound,
+
+    #[display(fmt = "the period must be one of day, week or month")]
+    InvalidReportPeriod,
+
+    #[display(fmt = "unknown time zone")]
+    UnknownTimeZone,
+
+    #[display(fmt = "the policy must be one of concurrent, single or auto-switch")]
+    InvalidTaskPolicy,
+
+    #[display(fmt = "another task is already in progress")]
+    AnotherTaskInProgress,
 }
 
 impl error::ResponseError for PunchTaskError {
@@ -45,6 +72,15 @@ impl error::ResponseError for PunchTaskError {
             PunchTaskError::TaskAlreadyInProgress => StatusCode::BAD_REQUEST,
             PunchTaskError::InProgressTaskNotFound => StatusCode::BAD_REQUEST,
             PunchTaskError::TaskNotFound => StatusCode::NOT_FOUND,
+            PunchTaskError::MissingTaskFilter => StatusCode::BAD_REQUEST,
+            PunchTaskError::InvalidTimeRange => StatusCode::BAD_REQUEST,
+            PunchTaskError::FutureSession => StatusCode::BAD_REQUEST,
+            PunchTaskError::OverlappingSession => StatusCode::CONFLICT,
+            PunchTaskError::SessionNotFound => StatusCode::NOT_FOUND,
+            PunchTaskError::InvalidReportPeriod => StatusCode::BAD_REQUEST,
+            PunchTaskError::UnknownTimeZone => StatusCode::BAD_REQUEST,
+            PunchTaskError::InvalidTaskPolicy => StatusCode::BAD_REQUEST,
+            PunchTaskError::AnotherTaskInProgress => StatusCode::CONFLICT,
         }
     }
 }